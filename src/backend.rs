@@ -1,9 +1,9 @@
-use std::{convert::Infallible, str::FromStr};
+use std::str::FromStr;
 
 pub mod eframe;
 pub mod layer_shell;
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum UiBackend {
     LayerShell,
     Eframe,
@@ -12,13 +12,14 @@ pub enum UiBackend {
 }
 
 impl FromStr for UiBackend {
-    type Err = Infallible;
+    type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         Ok(match s {
             "eframe" => Self::Eframe,
             "layer-shell" => Self::LayerShell,
-            _ => Self::InferFromEnv,
+            "auto" => Self::InferFromEnv,
+            _ => anyhow::bail!("unknown backend '{s}' (expected layer-shell, eframe or auto)"),
         })
     }
 }
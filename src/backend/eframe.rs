@@ -2,15 +2,17 @@ use crate::ui::LauncherApp;
 use anyhow::Result;
 use egui::{ViewportBuilder, ViewportCommand, vec2};
 
-pub(crate) fn run(app: LauncherApp) -> Result<()> {
+pub(crate) fn run(mut app: LauncherApp) -> Result<()> {
+    let (width, height) = app.size();
     let options = eframe::NativeOptions {
         viewport: ViewportBuilder::default()
             .with_app_id(env!("CARGO_PKG_NAME"))
-            .with_inner_size(vec2(800.0, 600.0))
+            .with_inner_size(vec2(width as f32, height as f32))
             .with_decorations(false)
             .with_transparent(true)
             .with_resizable(false)
-            .with_always_on_top(),
+            .with_always_on_top()
+            .with_visible(app.visible()),
         centered: true,
         renderer: eframe::Renderer::Glow,
         ..Default::default()
@@ -19,8 +21,12 @@ pub(crate) fn run(app: LauncherApp) -> Result<()> {
     eframe::run_native(
         env!("CARGO_PKG_NAME"),
         options,
-        Box::new(move |_| {
+        Box::new(move |creation| {
+            let ctx = creation.egui_ctx.clone();
+            app.serve(move || ctx.request_repaint());
+
             Ok(Box::new(X11App {
+                visible: app.visible(),
                 app,
                 received_focus: false,
             }))
@@ -32,23 +38,55 @@ pub(crate) fn run(app: LauncherApp) -> Result<()> {
 struct X11App {
     app: LauncherApp,
     received_focus: bool,
+
+    /// Whether the window is shown; a resident launcher hides it instead of closing
+    visible: bool,
 }
 
-impl eframe::App for X11App {
-    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
+impl X11App {
+    /// Follows requests of clients and the visibility of the app.
+    ///
+    /// eframe only calls `ui` while the window is shown, so this runs from `logic`, which is
+    /// called for every repaint, and a hidden daemon still gets shown again.
+    fn sync(&mut self, ctx: &egui::Context) {
+        self.app.handle_requests();
+        if self.app.take_focus_request() {
+            self.received_focus = false;
+        }
+
+        if self.app.closing() {
+            ctx.send_viewport_cmd(ViewportCommand::Close);
+        }
+
+        if self.app.visible() != self.visible {
+            self.visible = self.app.visible();
+            ctx.send_viewport_cmd(ViewportCommand::Visible(self.visible));
+            ctx.request_repaint();
+        }
+
         // Request native window focus until the window manager confirms it.
         // Requesting focus for the TextEdit only controls focus inside egui and
         // cannot make an inactive native window receive keyboard events.
-        if !self.received_focus {
-            self.received_focus = ui.input(|input| input.focused);
+        if self.visible && !self.received_focus {
+            self.received_focus = ctx.input(|input| input.focused);
             if !self.received_focus {
-                ui.ctx().send_viewport_cmd(ViewportCommand::Focus);
+                ctx.send_viewport_cmd(ViewportCommand::Focus);
             }
         }
+    }
+}
 
+impl eframe::App for X11App {
+    fn logic(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.sync(ctx);
+    }
+
+    fn ui(&mut self, ui: &mut egui::Ui, _frame: &mut eframe::Frame) {
         self.app.update(ui);
-        if self.app.closing() {
-            ui.ctx().send_viewport_cmd(ViewportCommand::Close);
+
+        // carried out by `logic` in the next frame
+        if self.app.closing() || self.app.visible() != self.visible {
+            ui.ctx().request_repaint();
         }
     }
 
@@ -56,3 +94,45 @@ impl eframe::App for X11App {
         self.app.clear_color()
     }
 }
+
+#[cfg(test)]
+mod test {
+    use egui::{RawInput, ViewportId};
+
+    use super::*;
+    use crate::ipc::Request;
+
+    /// Viewport commands sent by one frame of `sync`, the window being unfocused
+    fn sync(app: &mut X11App, ctx: &egui::Context) -> Vec<ViewportCommand> {
+        let input = RawInput {
+            focused: false,
+            ..Default::default()
+        };
+        let output = ctx.run_ui(input, |ui| app.sync(ui.ctx()));
+        output.viewport_output[&ViewportId::ROOT].commands.clone()
+    }
+
+    #[test]
+    fn test_show_hidden() {
+        let ctx = egui::Context::default();
+        let (app, requests) = LauncherApp::test_daemon();
+        let mut app = X11App {
+            visible: app.visible(),
+            app,
+            received_focus: false,
+        };
+        assert!(sync(&mut app, &ctx).is_empty());
+
+        requests.send(Request::Show).unwrap();
+        let commands = sync(&mut app, &ctx);
+        assert!(commands.contains(&ViewportCommand::Visible(true)));
+        assert!(commands.contains(&ViewportCommand::Focus));
+
+        // hidden like on Escape, then shown again without `ui` running in between
+        app.app.close();
+        assert!(sync(&mut app, &ctx).contains(&ViewportCommand::Visible(false)));
+
+        requests.send(Request::Toggle).unwrap();
+        assert!(sync(&mut app, &ctx).contains(&ViewportCommand::Visible(true)));
+    }
+}
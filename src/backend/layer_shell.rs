@@ -1,18 +1,28 @@
 use crate::ui::LauncherApp;
 use anyhow::{Context, Result};
-use calloop::{EventLoop, LoopHandle};
+use calloop::{
+    EventLoop, LoopHandle,
+    channel::{self, channel},
+    ping::make_ping,
+    timer::{TimeoutAction, Timer},
+};
 use calloop_wayland_source::WaylandSource;
-use egui::{Event, Key, Modifiers, RawInput};
+use egui::{
+    Event, Key, Modifiers, MouseWheelUnit, PointerButton, RawInput, TouchPhase, pos2, vec2,
+};
 use smithay_client_toolkit::{
     compositor::{CompositorHandler, CompositorState},
-    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_registry,
-    delegate_seat,
+    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
+    delegate_registry, delegate_seat, delegate_shm,
     output::{OutputHandler, OutputState},
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
     seat::{
         Capability, SeatHandler, SeatState,
         keyboard::{KeyEvent, KeyboardHandler, Keysym, Modifiers as SctkModifiers, RawModifiers},
+        pointer::{
+            CursorIcon, PointerEvent, PointerEventKind, PointerHandler, ThemeSpec, ThemedPointer,
+        },
     },
     shell::{
         WaylandSurface,
@@ -21,19 +31,18 @@ use smithay_client_toolkit::{
             LayerSurfaceConfigure,
         },
     },
+    shm::{Shm, ShmHandler},
 };
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use wayland_client::{
     Connection, QueueHandle,
     globals::registry_queue_init,
-    protocol::{wl_keyboard, wl_output, wl_seat, wl_surface},
+    protocol::{wl_keyboard, wl_output, wl_pointer, wl_seat, wl_surface},
 };
 use wlr_capture::render::Gpu;
 
-const WIDTH: u32 = 800;
-const HEIGHT: u32 = 600;
-
 pub fn run(app: LauncherApp) -> Result<()> {
+    let (width, height) = app.size();
     let connection = Connection::connect_to_env().context("failed to connect to Wayland")?;
     let (globals, events) =
         registry_queue_init(&connection).context("failed to read Wayland globals")?;
@@ -49,42 +58,69 @@ pub fn run(app: LauncherApp) -> Result<()> {
         CompositorState::bind(&globals, &queue).context("wl_compositor is unavailable")?;
     let layer_shell =
         LayerShell::bind(&globals, &queue).context("wlr-layer-shell is unavailable")?;
-    let surface = compositor.create_surface(&queue);
-    let layer = layer_shell.create_layer_surface(
-        &queue,
-        surface,
-        Layer::Overlay,
-        Some(env!("CARGO_PKG_NAME")),
-        None,
-    );
-    layer.set_size(WIDTH, HEIGHT);
-    layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
-    layer.set_exclusive_zone(-1);
-    layer.commit();
+    let shm = Shm::bind(&globals, &queue).context("wl_shm is unavailable")?;
 
     let mut state = State {
         registry: RegistryState::new(&globals),
         seats: SeatState::new(&globals, &queue),
         outputs: OutputState::new(&globals, &queue),
-        layer,
+        compositor,
+        layer_shell,
+        shm,
+        layer: None,
         keyboard: None,
+        pointer: None,
         egui: egui::Context::default(),
         app,
         gpu: None,
-        width: WIDTH,
-        height: HEIGHT,
+        width,
+        height,
         scale: 1,
         started: Instant::now(),
+        frame_pending: false,
+        redraw: false,
+        repaint_at: None,
         input: Vec::new(),
         modifiers: Modifiers::default(),
         connection,
+        queue,
         loop_handle: event_loop.handle(),
     };
 
+    // redraw when egui asks for it, e.g. after an icon was loaded in the background
+    let (repaints, repaint_source) = channel::<Duration>();
+    event_loop
+        .handle()
+        .insert_source(repaint_source, |event, _, state| {
+            if let channel::Event::Msg(delay) = event {
+                state.schedule_redraw(delay);
+            }
+        })
+        .map_err(|error| anyhow::anyhow!("failed to register repaint source: {}", error.error))?;
+    state.egui.set_request_repaint_callback(move |info| {
+        let _ = repaints.send(info.delay);
+    });
+
+    // requests of clients are handled below, the ping only wakes the loop up
+    let (ping, ping_source) = make_ping().context("failed to create request ping")?;
+    event_loop
+        .handle()
+        .insert_source(ping_source, |_, _, _| {})
+        .map_err(|error| anyhow::anyhow!("failed to register request source: {}", error.error))?;
+    state.app.serve(move || ping.ping());
+
+    if !state.app.visible() {
+        state.app.warm_up(&state.egui);
+    }
+
+    state.show_or_hide();
     while !state.app.closing() {
         event_loop
             .dispatch(None, &mut state)
             .context("event dispatch failed")?;
+
+        state.app.handle_requests();
+        state.show_or_hide();
     }
     Ok(())
 }
@@ -93,8 +129,14 @@ struct State {
     registry: RegistryState,
     seats: SeatState,
     outputs: OutputState,
-    layer: LayerSurface,
+    compositor: CompositorState,
+    layer_shell: LayerShell,
+    shm: Shm,
+
+    /// Surface of the window, while it's shown
+    layer: Option<LayerSurface>,
     keyboard: Option<wl_keyboard::WlKeyboard>,
+    pointer: Option<ThemedPointer>,
     egui: egui::Context,
     app: LauncherApp,
     gpu: Option<Gpu>,
@@ -102,21 +144,121 @@ struct State {
     height: u32,
     scale: u32,
     started: Instant,
+
+    /// Whether a frame callback of the last drawn frame is still awaited
+    frame_pending: bool,
+
+    /// Whether to draw again once the pending frame callback is done
+    redraw: bool,
+
+    /// When the timer of a delayed repaint fires
+    repaint_at: Option<Instant>,
+
     input: Vec<Event>,
     modifiers: Modifiers,
     connection: Connection,
+    queue: QueueHandle<State>,
     loop_handle: LoopHandle<'static, State>,
 }
 
 impl State {
+    /// Creates or destroys the layer surface to follow the visibility of the app.
+    fn show_or_hide(&mut self) {
+        match (self.app.visible(), &self.layer) {
+            (true, None) => {
+                (self.width, self.height) = self.app.size();
+                self.input.clear();
+
+                let surface = self.compositor.create_surface(&self.queue);
+                let layer = self.layer_shell.create_layer_surface(
+                    &self.queue,
+                    surface,
+                    Layer::Overlay,
+                    Some(env!("CARGO_PKG_NAME")),
+                    None,
+                );
+                layer.set_size(self.width, self.height);
+                layer.set_keyboard_interactivity(KeyboardInteractivity::Exclusive);
+                layer.set_exclusive_zone(-1);
+                layer.wl_surface().set_buffer_scale(self.scale as i32);
+                layer.commit();
+
+                // drawing starts with the first configure
+                self.layer = Some(layer);
+            }
+            (false, Some(_)) => {
+                self.gpu = None;
+                self.layer = None;
+                self.frame_pending = false;
+                self.redraw = false;
+            }
+            _ => {}
+        }
+    }
+
+    /// Draws after `delay`, paced by frame callbacks.
+    fn schedule_redraw(&mut self, delay: Duration) {
+        if delay.is_zero() {
+            self.request_redraw();
+            return;
+        }
+
+        // a delay too long to represent means no repaint is needed
+        let Some(deadline) = Instant::now().checked_add(delay) else {
+            return;
+        };
+        if self.repaint_at.is_some_and(|at| at <= deadline) {
+            return;
+        }
+
+        self.repaint_at = Some(deadline);
+        let timer =
+            self.loop_handle
+                .insert_source(Timer::from_deadline(deadline), |deadline, _, state| {
+                    if state.repaint_at == Some(deadline) {
+                        state.repaint_at = None;
+                    }
+                    state.request_redraw();
+                    TimeoutAction::Drop
+                });
+        if let Err(error) = timer {
+            log::warn!("failed to schedule repaint: {}", error.error);
+            self.repaint_at = None;
+        }
+    }
+
+    /// Draws now, or once the compositor is done with the last frame.
+    fn request_redraw(&mut self) {
+        if self.gpu.is_none() {
+            return;
+        }
+
+        if self.frame_pending {
+            self.redraw = true;
+        } else {
+            let connection = self.connection.clone();
+            self.draw(&connection);
+        }
+    }
+
     fn draw(&mut self, connection: &Connection) {
+        let Some(layer) = &self.layer else {
+            return;
+        };
+
+        // the buffer swap commits the surface, so the callback is requested before
+        let surface = layer.wl_surface();
+        surface.frame(&self.queue, surface.clone());
+        self.frame_pending = true;
+        self.redraw = false;
+
         let pixel_width = self.width * self.scale;
         let pixel_height = self.height * self.scale;
 
         let gpu = self.gpu.get_or_insert_with(|| {
             Gpu::new(
                 connection,
-                self.layer.wl_surface(),
+                layer.wl_surface(),
                 pixel_width as i32,
                 pixel_height as i32,
             )
@@ -144,7 +286,7 @@ impl State {
             |ui, _| app.update(ui),
         );
 
-        self.layer.commit();
+        layer.commit();
     }
 
     fn resize_gpu(&self) {
@@ -193,7 +335,9 @@ impl CompositorHandler for State {
         factor: i32,
     ) {
         self.scale = factor.max(1) as u32;
-        self.layer.wl_surface().set_buffer_scale(factor.max(1));
+        if let Some(layer) = &self.layer {
+            layer.wl_surface().set_buffer_scale(factor.max(1));
+        }
         self.resize_gpu();
     }
 
@@ -206,7 +350,18 @@ impl CompositorHandler for State {
     ) {
     }
 
-    fn frame(&mut self, _: &Connection, _: &QueueHandle<Self>, _: &wl_surface::WlSurface, _: u32) {}
+    fn frame(
+        &mut self,
+        connection: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_surface::WlSurface,
+        _: u32,
+    ) {
+        self.frame_pending = false;
+        if self.redraw {
+            self.draw(connection);
+        }
+    }
 
     fn surface_enter(
         &mut self,
@@ -278,6 +433,20 @@ impl SeatHandler for State {
                 )
                 .ok();
         }
+
+        if capability == Capability::Pointer && self.pointer.is_none() {
+            let surface = self.compositor.create_surface(queue);
+            self.pointer = self
+                .seats
+                .get_pointer_with_theme(
+                    queue,
+                    &seat,
+                    self.shm.wl_shm(),
+                    surface,
+                    ThemeSpec::default(),
+                )
+                .ok();
+        }
     }
 
     fn remove_capability(
@@ -290,6 +459,12 @@ impl SeatHandler for State {
         if capability == Capability::Keyboard {
             self.keyboard = None;
         }
+
+        if capability == Capability::Pointer
+            && let Some(pointer) = self.pointer.take()
+        {
+            pointer.pointer().release();
+        }
     }
 
     fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
@@ -374,6 +549,76 @@ impl KeyboardHandler for State {
     }
 }
 
+impl PointerHandler for State {
+    fn pointer_frame(
+        &mut self,
+        connection: &Connection,
+        _: &QueueHandle<Self>,
+        _: &wl_pointer::WlPointer,
+        events: &[PointerEvent],
+    ) {
+        for event in events {
+            let pos = pos2(event.position.0 as f32, event.position.1 as f32);
+
+            match event.kind {
+                PointerEventKind::Enter { .. } => {
+                    if let Some(pointer) = &self.pointer
+                        && let Err(err) = pointer.set_cursor(connection, CursorIcon::Default)
+                    {
+                        log::warn!("failed to set cursor: {err}");
+                    }
+                    self.input.push(Event::PointerMoved(pos));
+                }
+                PointerEventKind::Leave { .. } => self.input.push(Event::PointerGone),
+                PointerEventKind::Motion { .. } => self.input.push(Event::PointerMoved(pos)),
+                PointerEventKind::Press { button, .. }
+                | PointerEventKind::Release { button, .. } => {
+                    let Some(button) = map_button(button) else {
+                        continue;
+                    };
+
+                    self.input.push(Event::PointerButton {
+                        pos,
+                        button,
+                        pressed: matches!(event.kind, PointerEventKind::Press { .. }),
+                        modifiers: self.modifiers,
+                    });
+                }
+                PointerEventKind::Axis {
+                    horizontal,
+                    vertical,
+                    ..
+                } => {
+                    // wheels scroll by lines, touchpads by pixels
+                    let (unit, delta) = if horizontal.discrete != 0 || vertical.discrete != 0 {
+                        let delta = vec2(horizontal.discrete as f32, vertical.discrete as f32);
+                        (MouseWheelUnit::Line, delta)
+                    } else {
+                        let delta = vec2(horizontal.absolute as f32, vertical.absolute as f32);
+                        (MouseWheelUnit::Point, delta)
+                    };
+
+                    self.input.push(Event::MouseWheel {
+                        unit,
+                        delta: -delta,
+                        phase: TouchPhase::Move,
+                        modifiers: self.modifiers,
+                    });
+                }
+            }
+        }
+
+        // motion comes in faster than frames, the events wait for the next one
+        self.request_redraw();
+    }
+}
+
+impl ShmHandler for State {
+    fn shm_state(&mut self) -> &mut Shm {
+        &mut self.shm
+    }
+}
+
 impl OutputHandler for State {
     fn output_state(&mut self) -> &mut OutputState {
         &mut self.outputs
@@ -410,9 +655,21 @@ fn map_key(key: Keysym) -> Option<Key> {
     })
 }
 
+/// Maps Linux input event codes (`BTN_*`) to egui buttons.
+fn map_button(button: u32) -> Option<PointerButton> {
+    Some(match button {
+        0x110 => PointerButton::Primary,
+        0x111 => PointerButton::Secondary,
+        0x112 => PointerButton::Middle,
+        _ => return None,
+    })
+}
+
 delegate_compositor!(State);
 delegate_output!(State);
 delegate_seat!(State);
 delegate_keyboard!(State);
+delegate_pointer!(State);
+delegate_shm!(State);
 delegate_layer!(State);
 delegate_registry!(State);
@@ -0,0 +1,225 @@
+use std::{
+    collections::HashMap,
+    env,
+    path::{Path, PathBuf},
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{locale::Locale, model::Application};
+
+/// Bumped whenever the cached data changes shape, invalidating older caches
+const VERSION: u32 = 1;
+
+/// Parsed desktop entries, reused as long as their files don't change.
+#[derive(Debug, Default)]
+pub struct AppCache {
+    /// Entries read from the cache file
+    old: HashMap<String, Entry>,
+
+    /// Entries of files seen during this scan, written back on save
+    new: HashMap<String, Entry>,
+
+    /// Whether `new` differs from `old`
+    changed: bool,
+
+    locale: Option<String>,
+
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheFile {
+    version: u32,
+
+    /// Locale the names were translated for
+    locale: Option<String>,
+
+    #[serde(default)]
+    entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Entry {
+    /// Modification time, in nanoseconds since the Unix epoch
+    mtime: u64,
+
+    size: u64,
+
+    /// `None` for hidden entries
+    app: Option<Application>,
+}
+
+impl AppCache {
+    /// Loads the cache from `$XDG_CACHE_HOME/runi/apps.toml`; `rebuild` ignores its contents.
+    ///
+    /// A missing, outdated or corrupted cache is not an error, all files are parsed instead.
+    pub fn load_default(locale: Option<&Locale>, rebuild: bool) -> Self {
+        let mut cache = Self {
+            locale: locale.map(Locale::to_string),
+            ..Default::default()
+        };
+
+        let Some(path) = default_path() else {
+            log::warn!("neither XDG_CACHE_HOME nor HOME is set; app cache is disabled");
+            return cache;
+        };
+
+        if !rebuild && path.exists() {
+            match Self::load(&path) {
+                Ok(file) if file.version == VERSION && file.locale == cache.locale => {
+                    cache.old = file.entries;
+                }
+                Ok(_) => log::info!("app cache is outdated, rebuilding"),
+                Err(err) => log::warn!("failed to load app cache from {}: {err}", path.display()),
+            }
+        }
+
+        // a rebuilt cache is written even if nothing changed
+        cache.changed = cache.old.is_empty();
+        cache.path = Some(path);
+        cache
+    }
+
+    fn load(path: &Path) -> Result<CacheFile> {
+        let buf = std::fs::read_to_string(path)?;
+        let file = toml::from_str(&buf)?;
+
+        Ok(file)
+    }
+
+    /// Parses `file`, unless it's cached and hasn't changed since.
+    pub fn parse(&mut self, file: &Path, locale: Option<&Locale>) -> Result<Option<Application>> {
+        let metadata = file.metadata()?;
+        let mtime = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |mtime| mtime.as_nanos() as u64);
+        let size = metadata.len();
+
+        // paths that can't be stored in TOML are simply not cached
+        let Some(key) = file.to_str().map(str::to_owned) else {
+            return Application::from_freedesktop_file(file, locale);
+        };
+
+        if let Some(entry) = self.old.remove(&key)
+            && entry.mtime == mtime
+            && entry.size == size
+        {
+            let app = entry.app.clone();
+            self.new.insert(key, entry);
+
+            return Ok(app);
+        }
+
+        let app = Application::from_freedesktop_file(file, locale)?;
+
+        self.changed = true;
+        self.new.insert(
+            key,
+            Entry {
+                mtime,
+                size,
+                app: app.clone(),
+            },
+        );
+
+        Ok(app)
+    }
+
+    /// Writes entries of the files parsed since loading, if any of them changed.
+    pub fn save(mut self) -> Result<()> {
+        // leftover entries belong to removed (or now overridden) files
+        let changed = self.changed || !self.old.is_empty();
+        let Some(path) = self.path.take().filter(|_| changed) else {
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+
+        let file = CacheFile {
+            version: VERSION,
+            locale: self.locale,
+            entries: self.new,
+        };
+
+        // write to a temporary file first, so a crash never leaves a truncated cache; one per
+        // process, as instances started together may all update the cache
+        let tmp = path.with_extension(format!("toml.{}.tmp", std::process::id()));
+        std::fs::write(&tmp, toml::to_string(&file)?)?;
+        std::fs::rename(&tmp, &path)?;
+
+        Ok(())
+    }
+}
+
+fn default_path() -> Option<PathBuf> {
+    let cache_dir = match env::var_os("XDG_CACHE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+
+    Some(cache_dir.join("runi").join("apps.toml"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_cache() {
+        let dir = env::temp_dir().join(format!("runi-cache-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let desktop_file = dir.join("htop.desktop");
+        let cache_path = dir.join("apps.toml");
+        std::fs::copy(
+            format!("{}/test/htop.desktop", env!("CARGO_MANIFEST_DIR")),
+            &desktop_file,
+        )
+        .unwrap();
+
+        let cache = || AppCache {
+            old: AppCache::load(&cache_path).unwrap().entries,
+            path: Some(cache_path.clone()),
+            ..Default::default()
+        };
+
+        let mut first = AppCache {
+            path: Some(cache_path.clone()),
+            ..Default::default()
+        };
+        let htop = first.parse(&desktop_file, None).unwrap().unwrap();
+        first.save().unwrap();
+
+        // unchanged files come from the cache
+        let mut second = cache();
+        assert_eq!(
+            second.parse(&desktop_file, None).unwrap(),
+            Some(htop.clone())
+        );
+        assert!(!second.changed);
+        assert!(second.old.is_empty());
+
+        // changed files are parsed again
+        let mut content = std::fs::read_to_string(&desktop_file).unwrap();
+        content = content.replace("Name=Htop", "Name=Top");
+        std::fs::write(&desktop_file, content).unwrap();
+
+        let mut third = cache();
+        let top = third.parse(&desktop_file, None).unwrap().unwrap();
+        assert_eq!(top.name, "Top");
+        assert_eq!(top.exec, htop.exec);
+        assert!(third.changed);
+
+        std::fs::write(&cache_path, "garbage").unwrap();
+        assert!(AppCache::load(&cache_path).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,408 @@
+use std::{f64::consts, iter::Peekable, str::Chars};
+
+use anyhow::{Context, Result};
+
+/// How deep parentheses, signs and powers may nest, so the recursion can't overflow the stack
+const MAX_DEPTH: usize = 256;
+
+/// Evaluates `query` if it looks like arithmetic, formatting the result.
+///
+/// Plain numbers and words (which could be constants like `e`) are left alone, they're more
+/// likely the start of an app name.
+pub fn evaluate_query(query: &str) -> Option<String> {
+    let query = query.trim();
+    let based = ["0x", "0b", "0o"]
+        .iter()
+        .any(|prefix| query.starts_with(prefix));
+    let plain = query
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '.' || c == '_');
+
+    if query.is_empty() || (plain && !based) {
+        return None;
+    }
+
+    eval(query).ok().map(format)
+}
+
+/// Evaluates an arithmetic expression.
+///
+/// Supports `+ - * / %`, `^` (or `**`) for powers, parentheses, decimal, hex (`0x`), binary
+/// (`0b`) and octal (`0o`) numbers, the constants `pi`, `tau` and `e`, and the functions `sqrt`,
+/// `cbrt`, `abs`, `exp`, `ln`, `log` (base 10, or the base given second), `log2`, `sin`, `cos`,
+/// `tan`, `asin`, `acos`, `atan`, `floor`, `ceil`, `round`, `min` and `max`.
+pub fn eval(expr: &str) -> Result<f64> {
+    let mut parser = Parser {
+        chars: expr.chars().peekable(),
+        depth: 0,
+    };
+
+    let value = parser.expr()?;
+    parser.skip_whitespace();
+    if let Some(c) = parser.chars.peek() {
+        anyhow::bail!("unexpected '{c}'");
+    }
+
+    anyhow::ensure!(value.is_finite(), "the result is not a finite number");
+    Ok(value)
+}
+
+/// Formats `value` with at most 12 significant digits, in scientific notation if large or tiny.
+pub fn format(value: f64) -> String {
+    // rounding hides errors like 0.1 + 0.2 = 0.30000000000000004
+    let rounded: f64 = format!("{value:.11e}").parse().unwrap_or(value);
+
+    if rounded == 0.0 {
+        "0".to_owned()
+    } else if !(1e-9..1e15).contains(&rounded.abs()) {
+        format!("{rounded:e}")
+    } else {
+        rounded.to_string()
+    }
+}
+
+/// Recursive descent parser evaluating while parsing
+struct Parser<'a> {
+    chars: Peekable<Chars<'a>>,
+
+    /// Number of [`Parser::unary`] calls in progress, which every nesting goes through
+    depth: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    /// Consumes `c` (after whitespace) if it's next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        self.chars.next_if_eq(&c).is_some()
+    }
+
+    /// `term (('+' | '-') term)*`
+    fn expr(&mut self) -> Result<f64> {
+        let mut value = self.term()?;
+
+        loop {
+            if self.eat('+') {
+                value += self.term()?;
+            } else if self.eat('-') {
+                value -= self.term()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// `unary (('*' | '/' | '%') unary)*`
+    fn term(&mut self) -> Result<f64> {
+        let mut value = self.unary()?;
+
+        loop {
+            self.skip_whitespace();
+            let mut lookahead = self.chars.clone();
+            match (lookahead.next(), lookahead.next()) {
+                // `**` is a power, handled further down
+                (Some('*'), Some('*')) => return Ok(value),
+                (Some('*'), _) => {
+                    self.chars.next();
+                    value *= self.unary()?;
+                }
+                (Some('/'), _) => {
+                    self.chars.next();
+                    let divisor = self.unary()?;
+                    anyhow::ensure!(divisor != 0.0, "division by zero");
+                    value /= divisor;
+                }
+                (Some('%'), _) => {
+                    self.chars.next();
+                    let divisor = self.unary()?;
+                    anyhow::ensure!(divisor != 0.0, "division by zero");
+                    value %= divisor;
+                }
+                _ => return Ok(value),
+            }
+        }
+    }
+
+    /// `('-' | '+') unary | power`; `-2^2` is `-(2^2)`
+    fn unary(&mut self) -> Result<f64> {
+        anyhow::ensure!(
+            self.depth < MAX_DEPTH,
+            "the expression is nested too deeply"
+        );
+        self.depth += 1;
+
+        let value = if self.eat('-') {
+            self.unary().map(|value| -value)
+        } else if self.eat('+') {
+            self.unary()
+        } else {
+            self.power()
+        };
+
+        self.depth -= 1;
+        value
+    }
+
+    /// `primary (('^' | '**') unary)?`, right-associative
+    fn power(&mut self) -> Result<f64> {
+        let base = self.primary()?;
+
+        self.skip_whitespace();
+        let mut lookahead = self.chars.clone();
+        let operator_len = match (lookahead.next(), lookahead.next()) {
+            (Some('^'), _) => 1,
+            (Some('*'), Some('*')) => 2,
+            _ => return Ok(base),
+        };
+        for _ in 0..operator_len {
+            self.chars.next();
+        }
+
+        Ok(base.powf(self.unary()?))
+    }
+
+    /// A number, constant, function call or expression in parentheses
+    fn primary(&mut self) -> Result<f64> {
+        self.skip_whitespace();
+
+        match self.chars.peek().copied() {
+            Some('(') => {
+                self.chars.next();
+                let value = self.expr()?;
+                anyhow::ensure!(self.eat(')'), "missing ')'");
+                Ok(value)
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => self.number(),
+            Some(c) if c.is_alphabetic() => {
+                let name = self.take_while(|c| c.is_alphanumeric());
+                if self.eat('(') {
+                    let args = self.args()?;
+                    call(&name, &args)
+                } else {
+                    constant(&name)
+                }
+            }
+            Some(c) => anyhow::bail!("unexpected '{c}'"),
+            None => anyhow::bail!("unexpected end of expression"),
+        }
+    }
+
+    /// Comma-separated arguments up to the closing parenthesis
+    fn args(&mut self) -> Result<Vec<f64>> {
+        let mut args = vec![self.expr()?];
+        while self.eat(',') {
+            args.push(self.expr()?);
+        }
+        anyhow::ensure!(self.eat(')'), "missing ')'");
+
+        Ok(args)
+    }
+
+    fn number(&mut self) -> Result<f64> {
+        let mut lookahead = self.chars.clone();
+        let radix = match (lookahead.next(), lookahead.next()) {
+            (Some('0'), Some('x' | 'X')) => 16,
+            (Some('0'), Some('b' | 'B')) => 2,
+            (Some('0'), Some('o' | 'O')) => 8,
+            _ => 10,
+        };
+
+        if radix != 10 {
+            self.chars.nth(1);
+            let digits = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+            let digits = digits.replace('_', "");
+            let value = u64::from_str_radix(&digits, radix)
+                .with_context(|| format!("invalid base {radix} number '{digits}'"))?;
+
+            return Ok(value as f64);
+        }
+
+        let mut literal = self.take_while(|c| c.is_ascii_digit() || c == '.' || c == '_');
+
+        // exponent, e.g. `1e-3`; an `e` not followed by digits is left to be an error
+        let mut lookahead = self.chars.clone();
+        if let Some('e' | 'E') = lookahead.next() {
+            let sign = lookahead.next_if(|c| *c == '-' || *c == '+');
+            if lookahead.peek().is_some_and(char::is_ascii_digit) {
+                self.chars.next();
+                literal.push('e');
+                if let Some(sign) = sign {
+                    self.chars.next();
+                    literal.push(sign);
+                }
+                literal += &self.take_while(|c| c.is_ascii_digit());
+            }
+        }
+
+        literal
+            .replace('_', "")
+            .parse()
+            .with_context(|| format!("invalid number '{literal}'"))
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut taken = String::new();
+        while let Some(c) = self.chars.next_if(|c| predicate(*c)) {
+            taken.push(c);
+        }
+
+        taken
+    }
+}
+
+fn constant(name: &str) -> Result<f64> {
+    Ok(match name {
+        "pi" => consts::PI,
+        "tau" => consts::TAU,
+        "e" => consts::E,
+        _ => anyhow::bail!("unknown constant '{name}'"),
+    })
+}
+
+fn call(name: &str, args: &[f64]) -> Result<f64> {
+    let unary = |f: fn(f64) -> f64| match args {
+        [x] => Ok(f(*x)),
+        _ => anyhow::bail!("{name} takes 1 argument, got {}", args.len()),
+    };
+
+    match name {
+        "sqrt" => unary(f64::sqrt),
+        "cbrt" => unary(f64::cbrt),
+        "abs" => unary(f64::abs),
+        "exp" => unary(f64::exp),
+        "ln" => unary(f64::ln),
+        "log2" => unary(f64::log2),
+        "sin" => unary(f64::sin),
+        "cos" => unary(f64::cos),
+        "tan" => unary(f64::tan),
+        "asin" => unary(f64::asin),
+        "acos" => unary(f64::acos),
+        "atan" => unary(f64::atan),
+        "floor" => unary(f64::floor),
+        "ceil" => unary(f64::ceil),
+        "round" => unary(f64::round),
+        "log" => match args {
+            [x] => Ok(x.log10()),
+            [x, base] => Ok(x.log(*base)),
+            _ => anyhow::bail!("log takes 1 or 2 arguments, got {}", args.len()),
+        },
+        "min" => Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+        "max" => Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+        _ => anyhow::bail!("unknown function '{name}'"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn assert_eval(expr: &str, expected: f64) {
+        let value = eval(expr).unwrap_or_else(|err| panic!("{expr}: {err}"));
+        assert!(
+            (value - expected).abs() < 1e-9,
+            "{expr} = {value}, expected {expected}"
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        assert_eval("1 + 2 * 3", 7.0);
+        assert_eval("(1 + 2) * 3", 9.0);
+        assert_eval("10 - 4 - 3", 3.0);
+        assert_eval("2 * 3 % 4", 2.0);
+        assert_eval("2^10 / 4", 256.0);
+        assert_eval("2 ** 3 * 2", 16.0);
+        assert_eval("2^3^2", 512.0);
+        assert_eval("-2^2", -4.0);
+        assert_eval("2^-1", 0.5);
+        assert_eval("--3 + +1", 4.0);
+        assert_eval(" ( ( 1 ) ) ", 1.0);
+    }
+
+    #[test]
+    fn test_numbers() {
+        assert_eval("0x1f + 7", 38.0);
+        assert_eval("0b1010", 10.0);
+        assert_eval("0o17", 15.0);
+        assert_eval("0xFF_FF", 65535.0);
+        assert_eval("1_000 * 1.5", 1500.0);
+        assert_eval(".5 + 1.", 1.5);
+        assert_eval("1e3 + 2.5E-1", 1000.25);
+    }
+
+    #[test]
+    fn test_functions() {
+        assert_eval("sqrt(2)*pi", 2f64.sqrt() * consts::PI);
+        assert_eval("sqrt(16) + abs(-2)", 6.0);
+        assert_eval("log(1000)", 3.0);
+        assert_eval("log(8, 2)", 3.0);
+        assert_eval("ln(e)", 1.0);
+        assert_eval("sin(pi / 2)", 1.0);
+        assert_eval("max(1, 5, 3) - min(4, 2)", 3.0);
+        assert_eval("round(2.5) + floor(-0.5) + ceil(0.1)", 3.0);
+    }
+
+    #[test]
+    fn test_errors() {
+        for expr in [
+            "",
+            "1 +",
+            "(1 + 2",
+            "1 + 2)",
+            "2 3",
+            "1 / 0",
+            "5 % 0",
+            "foo(1)",
+            "bar",
+            "sqrt(1, 2)",
+            "log()",
+            "0x",
+            "0b102",
+            "1.2.3",
+            "sqrt(-1)",
+            "2 $ 3",
+        ] {
+            assert!(eval(expr).is_err(), "{expr} should fail");
+        }
+    }
+
+    #[test]
+    fn test_depth() {
+        let nested = |depth| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+
+        assert_eval(&nested(100), 1.0);
+        assert_eval(&format!("{}1", "-".repeat(100)), 1.0);
+        assert!(eval(&nested(MAX_DEPTH)).is_err());
+        assert!(eval(&nested(100_000)).is_err());
+        assert!(eval(&format!("{}1", "-".repeat(100_000))).is_err());
+        assert!(eval(&"2^".repeat(100_000)).is_err());
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(format(1024.0), "1024");
+        assert_eq!(format(-3.0), "-3");
+        assert_eq!(format(0.1 + 0.2), "0.3");
+        assert_eq!(format(2.0 / 3.0), "0.666666666667");
+        assert_eq!(format(1e20), "1e20");
+        assert_eq!(format(1.5e-12), "1.5e-12");
+        assert_eq!(format(-0.0), "0");
+    }
+
+    #[test]
+    fn test_evaluate_query() {
+        assert_eq!(evaluate_query("2^10 / 4"), Some("256".to_owned()));
+        assert_eq!(evaluate_query("0x1f"), Some("31".to_owned()));
+        assert_eq!(evaluate_query("sqrt(4)"), Some("2".to_owned()));
+
+        // likely the start of app names
+        assert_eq!(evaluate_query("firefox"), None);
+        assert_eq!(evaluate_query("e"), None);
+        assert_eq!(evaluate_query("2048"), None);
+        assert_eq!(evaluate_query("firefox-esr"), None);
+        assert_eq!(evaluate_query("  "), None);
+    }
+}
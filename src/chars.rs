@@ -0,0 +1,270 @@
+use std::{collections::HashMap, ops::RangeInclusive, time::Instant};
+
+use crate::{
+    fuzzy::{Match, fuzzy_match},
+    loader,
+};
+
+/// CLDR annotations (keywords of emoji and symbols) in a data dir, as installed by e.g. `unicode-cldr-core`
+const ANNOTATIONS: &str = "unicode/cldr/common/annotations/en.xml";
+
+/// Blocks of symbols worth picking besides emoji: punctuation, letters with diacritics, Greek,
+/// currency, arrows, math, box drawing, shapes and dingbats
+const BLOCKS: &[RangeInclusive<u32>] = &[
+    0x00a1..=0x00ff,
+    0x0370..=0x03ff,
+    0x2010..=0x205e,
+    0x2070..=0x209f,
+    0x20a0..=0x20c0,
+    0x2100..=0x218b,
+    0x2190..=0x23ff,
+    0x2460..=0x27ff,
+    0x2900..=0x2bff,
+];
+
+/// Emoji or another Unicode character, picked by its name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Character {
+    /// The character itself; emoji may take several code points
+    pub glyph: String,
+
+    /// CLDR short name of emoji, the lowercase Unicode name of other characters
+    pub name: String,
+
+    /// CLDR keywords and GitHub shortcodes of emoji, the code point of other characters
+    pub keywords: Vec<String>,
+}
+
+impl Character {
+    fn from_emoji(emoji: &emojis::Emoji, annotations: &Annotations) -> Self {
+        // annotations leave out the variation selector of emoji like ☀️
+        let glyph = emoji.as_str();
+        let mut keywords = annotations
+            .get(glyph)
+            .or_else(|| annotations.get(&glyph.replace('\u{fe0f}', "")))
+            .cloned()
+            .unwrap_or_default();
+
+        for shortcode in emoji.shortcodes() {
+            if !keywords.iter().any(|keyword| keyword == shortcode) {
+                keywords.push(shortcode.to_owned());
+            }
+        }
+
+        Self {
+            glyph: glyph.to_owned(),
+            name: emoji.name().to_owned(),
+            keywords,
+        }
+    }
+
+    /// The character with its Unicode name, unless it has none or is an emoji.
+    fn from_char(c: char, annotations: &Annotations) -> Option<Self> {
+        let glyph = c.to_string();
+        if emojis::get(&glyph).is_some() {
+            return None;
+        }
+
+        let name = unicode_names2::name(c)?.to_string().to_lowercase();
+
+        let mut keywords = annotations.get(&glyph).cloned().unwrap_or_default();
+        keywords.push(format!("U+{:04X}", c as u32));
+
+        Some(Self {
+            glyph,
+            name,
+            keywords,
+        })
+    }
+
+    /// Fuzzy matches the name and, with a lower weight, the keywords against `filter`.
+    ///
+    /// A keyword typed out in full counts like the whole name, e.g. "cool" finds 😎 before 🆒.
+    pub fn score(&self, filter: &str) -> Option<Match> {
+        const KEYWORDS_WEIGHT: f64 = 0.7;
+
+        let keywords = self
+            .keywords
+            .iter()
+            .filter_map(|keyword| {
+                if keyword.eq_ignore_ascii_case(filter.trim()) {
+                    return Some(1.0);
+                }
+                fuzzy_match(keyword, filter).map(|m| m.score * KEYWORDS_WEIGHT)
+            })
+            .fold(0.0, f64::max);
+
+        match fuzzy_match(&self.name, filter) {
+            Some(name) if name.score >= keywords => Some(name),
+            _ => (keywords > 0.0).then(|| Match {
+                score: keywords,
+                indices: vec![],
+            }),
+        }
+    }
+}
+
+/// Keywords of emoji and symbols by their glyph
+type Annotations = HashMap<String, Vec<String>>;
+
+/// Loads all emoji (in CLDR order) followed by the characters of [`BLOCKS`].
+///
+/// Emoji keywords come from the CLDR annotations of the first data dir that has them.
+pub fn load_chars() -> Vec<Character> {
+    let dirs = loader::app_dirs();
+    let annotations = dirs
+        .user
+        .iter()
+        .chain(&dirs.system)
+        .map(|dir| dir.join(ANNOTATIONS))
+        .find_map(|path| {
+            let xml = std::fs::read_to_string(&path).ok()?;
+            log::info!("emoji keywords: {}", path.display());
+            Some(parse_annotations(&xml))
+        });
+    if annotations.is_none() {
+        log::info!("no CLDR annotations found, emoji are searched by name and shortcode only");
+    }
+
+    load_chars_with(&annotations.unwrap_or_default())
+}
+
+fn load_chars_with(annotations: &Annotations) -> Vec<Character> {
+    let timer = Instant::now();
+
+    let chars: Vec<_> = emojis::iter()
+        .map(|emoji| Character::from_emoji(emoji, annotations))
+        .chain(
+            BLOCKS
+                .iter()
+                .flat_map(|block| block.clone().filter_map(char::from_u32))
+                .filter_map(|c| Character::from_char(c, annotations)),
+        )
+        .collect();
+
+    log::info!(
+        "loaded {} characters in {}ms",
+        chars.len(),
+        timer.elapsed().as_millis()
+    );
+
+    chars
+}
+
+/// Reads the keywords of a CLDR annotations file, one `<annotation>` element per line.
+///
+/// The `tts` annotations are the short names, which emoji have already.
+fn parse_annotations(xml: &str) -> Annotations {
+    let mut annotations = Annotations::new();
+
+    for line in xml.lines() {
+        let Some(rest) = line.trim().strip_prefix("<annotation cp=\"") else {
+            continue;
+        };
+        let Some((glyph, rest)) = rest.split_once('"') else {
+            continue;
+        };
+        let Some(keywords) = rest
+            .strip_prefix('>')
+            .and_then(|rest| rest.strip_suffix("</annotation>"))
+        else {
+            continue;
+        };
+
+        let keywords = keywords
+            .split('|')
+            .map(|keyword| unescape(keyword.trim()))
+            .filter(|keyword| !keyword.is_empty())
+            .collect();
+        annotations.insert(unescape(glyph), keywords);
+    }
+
+    annotations
+}
+
+/// Replaces the predefined XML entities.
+fn unescape(s: &str) -> String {
+    s.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn chars() -> Vec<Character> {
+        let xml = include_str!("../test/annotations.xml");
+        load_chars_with(&parse_annotations(xml))
+    }
+
+    fn find(chars: &[Character], glyph: &str) -> Character {
+        chars
+            .iter()
+            .find(|c| c.glyph == glyph)
+            .unwrap_or_else(|| panic!("{glyph} not found"))
+            .clone()
+    }
+
+    #[test]
+    fn test_load_chars() {
+        let chars = chars();
+
+        // the `sunglasses` shortcode is a CLDR keyword already
+        assert_eq!(
+            find(&chars, "😎"),
+            Character {
+                glyph: "😎".to_owned(),
+                name: "smiling face with sunglasses".to_owned(),
+                keywords: ["bright", "cool", "face", "sun", "sunglasses"]
+                    .map(str::to_owned)
+                    .to_vec(),
+            }
+        );
+        assert_eq!(find(&chars, "→").name, "rightwards arrow");
+        assert_eq!(find(&chars, "→").keywords[..2], ["arrow", "cardinal"]);
+        assert_eq!(find(&chars, "€").keywords, ["U+20AC"]);
+        assert!(find(&chars, "☀️").keywords.contains(&"sunny".to_owned()));
+        assert!(find(&chars, "🍝").keywords.contains(&"pasta".to_owned()));
+
+        // without annotations, emoji keep their shortcodes
+        let plain = load_chars_with(&Annotations::new());
+        assert_eq!(find(&plain, "😎").keywords, ["sunglasses"]);
+
+        // emoji from the symbol blocks are listed once, as emoji
+        assert_eq!(chars.iter().filter(|c| c.glyph == "☀️").count(), 1);
+        assert!(chars.iter().all(|c| c.glyph != "☀"));
+    }
+
+    #[test]
+    fn test_parse_annotations() {
+        let annotations = parse_annotations(include_str!("../test/annotations.xml"));
+
+        assert_eq!(annotations["😀"], ["face", "grin", "grinning face"]);
+        assert_eq!(annotations["🪨"][2], "rock & roll");
+        // one entry per glyph, the `tts` short names are skipped
+        assert_eq!(annotations.len(), 7);
+    }
+
+    #[test]
+    fn test_score() {
+        let chars = chars();
+        // ties keep the list order like in the window, `max_by` picks the last one of them
+        let best = |filter| {
+            chars
+                .iter()
+                .rev()
+                .filter_map(|c| Some((c, c.score(filter)?.score)))
+                .max_by(|(_, a), (_, b)| a.total_cmp(b))
+                .map(|(c, _)| c.glyph.as_str())
+        };
+
+        assert_eq!(best("rightwards arrow"), Some("→"));
+        assert_eq!(best("thumbsup"), Some("👍"));
+        assert_eq!(best("cool"), Some("😎"));
+        assert_eq!(best("happy"), Some("😊"));
+        assert_eq!(best("U+00B0"), Some("°"));
+    }
+}
@@ -0,0 +1,265 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+
+use crate::{backend::UiBackend, config, ui::Mode};
+
+pub const HELP: &str = "\
+Application launcher for X11 and wlr-layer-shell Wayland compositors
+
+Usage: runi [OPTIONS] [COMMAND]
+
+Commands:
+  list           Print discovered applications
+  launch <NAME>  Launch the best matching application without opening a window
+
+Options:
+  -b, --backend <BACKEND>  UI backend: layer-shell, eframe or auto [default: auto]
+  -c, --config <PATH>      Config file [default: $XDG_CONFIG_HOME/runi/config.toml]
+  -m, --mode <MODE>        What to pick from: apps, run, chars or dmenu [default: apps]
+      --dmenu              Same as --mode dmenu
+      --print-query        dmenu: print the typed text if nothing matches or on Shift+Enter
+      --print              chars: print the picked character instead of copying it
+  -p, --prompt <TEXT>      Placeholder of the search field
+      --width <PIXELS>     Window width
+      --height <PIXELS>    Window height
+      --rebuild-cache      Parse all desktop files again instead of using the app cache
+      --daemon             Stay in the background, showing the window when runi is run again
+      --toggle             Close the window if it's already shown, whatever the config says
+  -h, --help               Print help
+  -V, --version            Print version
+";
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    pub backend: UiBackend,
+    pub config: Option<PathBuf>,
+    pub mode: Mode,
+    pub prompt: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub rebuild_cache: bool,
+    pub daemon: bool,
+    pub toggle: bool,
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub enum Command {
+    /// Open the launcher window
+    #[default]
+    Ui,
+    List,
+    Launch(String),
+    Help,
+    Version,
+}
+
+impl Cli {
+    /// Parses arguments, not including the program name.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut cli = Self::default();
+        let mut print_query = false;
+        let mut print = false;
+        let mut positional = vec![];
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') || arg == "-" {
+                positional.push(arg);
+                continue;
+            }
+
+            // accept both `--option value` and `--option=value`
+            let (option, inline_value) = match arg.split_once('=') {
+                Some((option, value)) if option.starts_with("--") => {
+                    (option.to_owned(), Some(value.to_owned()))
+                }
+                _ => (arg, None),
+            };
+            let mut value = || {
+                inline_value
+                    .clone()
+                    .or_else(|| args.next())
+                    .with_context(|| format!("option '{option}' requires a value"))
+            };
+
+            match option.as_str() {
+                "-b" | "--backend" => cli.backend = value()?.parse()?,
+                "-c" | "--config" => cli.config = Some(value()?.into()),
+                "-m" | "--mode" => cli.mode = value()?.parse()?,
+                "--dmenu" => cli.mode = Mode::Dmenu { print_query: false },
+                "--print-query" => print_query = true,
+                "--print" => print = true,
+                "-p" | "--prompt" => cli.prompt = Some(value()?),
+                "--width" => cli.width = Some(parse_size(&option, &value()?)?),
+                "--height" => cli.height = Some(parse_size(&option, &value()?)?),
+                "--rebuild-cache" => cli.rebuild_cache = true,
+                "--daemon" => cli.daemon = true,
+                "--toggle" => cli.toggle = true,
+                "-h" | "--help" => cli.command = Command::Help,
+                "-V" | "--version" => cli.command = Command::Version,
+                _ => anyhow::bail!("unknown option '{option}'"),
+            }
+
+            if inline_value.is_some() && !option_takes_value(&option) {
+                anyhow::bail!("option '{option}' doesn't take a value");
+            }
+        }
+
+        if let Mode::Dmenu {
+            print_query: dmenu_print_query,
+        } = &mut cli.mode
+        {
+            *dmenu_print_query = print_query;
+        } else if print_query {
+            anyhow::bail!("'--print-query' requires dmenu mode");
+        }
+
+        if let Mode::Chars { print: chars_print } = &mut cli.mode {
+            *chars_print = print;
+        } else if print {
+            anyhow::bail!("'--print' requires chars mode");
+        }
+
+        if cli.daemon && cli.mode != Mode::Apps {
+            anyhow::bail!("'--daemon' requires apps mode");
+        }
+
+        // --help and --version win over everything else
+        if matches!(cli.command, Command::Help | Command::Version) {
+            return Ok(cli);
+        }
+
+        let mut positional = positional.into_iter();
+        cli.command = match positional.next().as_deref() {
+            None => Command::Ui,
+            Some("list") => Command::List,
+            Some("launch") => Command::Launch(
+                positional
+                    .next()
+                    .context("'launch' requires an application name")?,
+            ),
+            Some(command) => anyhow::bail!("unknown command '{command}'"),
+        };
+
+        if let Some(arg) = positional.next() {
+            anyhow::bail!("unexpected argument '{arg}'");
+        }
+
+        Ok(cli)
+    }
+}
+
+fn option_takes_value(option: &str) -> bool {
+    matches!(
+        option,
+        "--backend" | "--config" | "--mode" | "--prompt" | "--width" | "--height"
+    )
+}
+
+fn parse_size(option: &str, value: &str) -> Result<u32> {
+    value
+        .parse()
+        .ok()
+        .and_then(config::valid_size)
+        .with_context(|| {
+            format!("invalid value '{value}' for '{option}': expected a positive number")
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli> {
+        Cli::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn test_options() {
+        let cli = parse(&[
+            "--backend",
+            "eframe",
+            "-c",
+            "/tmp/runi.toml",
+            "--prompt=Run",
+            "--width",
+            "640",
+            "--height=480",
+            "--rebuild-cache",
+            "--daemon",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            cli,
+            Cli {
+                command: Command::Ui,
+                backend: UiBackend::Eframe,
+                config: Some("/tmp/runi.toml".into()),
+                mode: Mode::Apps,
+                prompt: Some("Run".to_owned()),
+                width: Some(640),
+                height: Some(480),
+                rebuild_cache: true,
+                daemon: true,
+                toggle: false,
+            }
+        );
+    }
+
+    #[test]
+    fn test_dmenu() {
+        assert_eq!(
+            parse(&["--print-query", "--dmenu"]).unwrap().mode,
+            Mode::Dmenu { print_query: true }
+        );
+        assert_eq!(
+            parse(&["-m", "dmenu"]).unwrap().mode,
+            Mode::Dmenu { print_query: false }
+        );
+        assert!(parse(&["--print-query"]).is_err());
+        assert_eq!(parse(&["--mode=run"]).unwrap().mode, Mode::Run);
+        assert!(parse(&["--print-query", "-m", "run"]).is_err());
+    }
+
+    #[test]
+    fn test_chars() {
+        assert_eq!(
+            parse(&["-m", "chars"]).unwrap().mode,
+            Mode::Chars { print: false }
+        );
+        assert_eq!(
+            parse(&["--print", "--mode=chars"]).unwrap().mode,
+            Mode::Chars { print: true }
+        );
+        assert!(parse(&["--print"]).is_err());
+        assert!(parse(&["--print", "--dmenu"]).is_err());
+    }
+
+    #[test]
+    fn test_commands() {
+        assert_eq!(parse(&[]).unwrap().command, Command::Ui);
+        assert_eq!(parse(&["list"]).unwrap().command, Command::List);
+        assert_eq!(
+            parse(&["launch", "firefox", "-b", "auto"]).unwrap().command,
+            Command::Launch("firefox".to_owned())
+        );
+        assert_eq!(parse(&["list", "--help"]).unwrap().command, Command::Help);
+        assert_eq!(parse(&["-V"]).unwrap().command, Command::Version);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert!(parse(&["--frobnicate"]).is_err());
+        assert!(parse(&["eframe"]).is_err());
+        assert!(parse(&["--backend", "gtk"]).is_err());
+        assert!(parse(&["--backend"]).is_err());
+        assert!(parse(&["--width", "-5"]).is_err());
+        assert!(parse(&["--dmenu=yes"]).is_err());
+        assert!(parse(&["--daemon", "--dmenu"]).is_err());
+        assert!(parse(&["launch"]).is_err());
+        assert!(parse(&["launch", "a", "b"]).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use std::{
     borrow::Cow,
     collections::HashMap,
+    env,
     io::{BufReader, Read},
     path::{Path, PathBuf},
 };
@@ -8,15 +9,156 @@ use std::{
 use egui::Color32;
 use serde::{Deserialize, Deserializer};
 
-use crate::model::Exec;
+use crate::model::{Exec, TerminalCommand};
 
-pub const SELECTION_COLOR: Color32 = Color32::DARK_RED;
-pub const BACKGROUND_COLOR: Color32 = Color32::TRANSPARENT;
-
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Clone, Deserialize, Default)]
 pub struct Config {
-    #[serde(rename = "patch")]
+    #[serde(rename = "patch", default)]
     pub patches: HashMap<PathBuf, Patch>,
+
+    #[serde(default)]
+    pub theme: Theme,
+
+    /// Show entries hidden by `TryExec`, `OnlyShowIn` or `NotShowIn` greyed-out, with the reason
+    #[serde(default)]
+    pub show_filtered: bool,
+
+    /// Terminal emulator running `Terminal=true` apps, e.g. `alacritty -e {cmd}`
+    #[serde(default, deserialize_with = "deserialize_terminal")]
+    pub terminal: Option<TerminalCommand>,
+
+    /// What running `runi` again does while the window is shown
+    #[serde(default)]
+    pub second_invocation: SecondInvocation,
+
+    /// How apps are started
+    #[serde(default)]
+    pub launch: LaunchMethod,
+
+    /// File the output of detached apps is appended to; discarded if not set
+    #[serde(default)]
+    pub launch_log: Option<PathBuf>,
+
+    /// Rows offered below the matches for the typed text
+    #[serde(rename = "fallback", default)]
+    pub fallbacks: Vec<Fallback>,
+
+    /// Keywords typed before the text to pass it to a command or URL
+    #[serde(rename = "shortcut", default)]
+    pub shortcuts: Vec<Shortcut>,
+}
+
+/// Row doing something with the typed text, e.g. `{ type = "search", name = "DuckDuckGo",
+/// url = "https://duckduckgo.com/?q={query}" }`
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Fallback {
+    /// Run the text with `sh -c`
+    Shell,
+
+    /// Run the text with `sh -c` in the terminal emulator, staying open afterwards
+    Terminal,
+
+    /// Open `url` with `xdg-open`, `{query}` replaced with the URL-encoded text
+    Search { name: String, url: String },
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SecondInvocation {
+    /// Close the window
+    #[default]
+    Toggle,
+
+    /// Focus the window again
+    Focus,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LaunchMethod {
+    /// Replace the launcher with the app
+    #[default]
+    Exec,
+
+    /// Start the app in a new session and exit, showing errors in the window
+    Detach,
+
+    /// Like `detach`, in a systemd scope of its own if the user manager is available
+    Systemd,
+}
+
+/// Keyword leading the typed text, e.g. `gh` in `gh runi`
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+pub struct Shortcut {
+    pub keyword: String,
+
+    /// Label of the row; the keyword if not set
+    pub name: Option<String>,
+
+    #[serde(flatten)]
+    pub target: ShortcutTarget,
+}
+
+/// What a shortcut does with the text after the keyword, substituted for `{query}`
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(try_from = "ShortcutFields")]
+pub enum ShortcutTarget {
+    /// Open the URL with `xdg-open`, the text URL-encoded
+    Url { url: String },
+
+    /// Run the command line with `sh -c`, the text quoted as a single argument
+    Command {
+        command: String,
+
+        /// Whether the command runs in the terminal emulator
+        terminal: bool,
+    },
+}
+
+/// Keys of a shortcut table, checked to make up a valid [`ShortcutTarget`]
+#[derive(Deserialize)]
+struct ShortcutFields {
+    url: Option<String>,
+    command: Option<String>,
+    terminal: Option<bool>,
+}
+
+impl TryFrom<ShortcutFields> for ShortcutTarget {
+    type Error = &'static str;
+
+    fn try_from(fields: ShortcutFields) -> Result<Self, Self::Error> {
+        match fields {
+            ShortcutFields {
+                url: Some(url),
+                command: None,
+                terminal: None,
+            } => Ok(Self::Url { url }),
+            ShortcutFields {
+                url: None,
+                command: Some(command),
+                terminal,
+            } => Ok(Self::Command {
+                command,
+                terminal: terminal.unwrap_or_default(),
+            }),
+            ShortcutFields {
+                url: Some(_),
+                command: None,
+                terminal: Some(_),
+            } => Err("`terminal` only applies to a shortcut `command`"),
+            ShortcutFields {
+                url: Some(_),
+                command: Some(_),
+                ..
+            } => Err("a shortcut takes either `url` or `command`, not both"),
+            ShortcutFields {
+                url: None,
+                command: None,
+                ..
+            } => Err("a shortcut needs `url` or `command`"),
+        }
+    }
 }
 
 impl Config {
@@ -30,19 +172,165 @@ impl Config {
 
         Ok(cfg)
     }
+
+    /// Loads the config from `path`, or from the default location if not given.
+    ///
+    /// A missing or broken config at the default location is not an error.
+    pub fn load_or_default(path: Option<&Path>) -> anyhow::Result<Self> {
+        if let Some(path) = path {
+            return Self::load(path)
+                .map_err(|err| anyhow::anyhow!("failed to load config {}: {err}", path.display()));
+        }
+
+        let Some(path) = Self::default_path().filter(|path| path.exists()) else {
+            return Ok(Self::default());
+        };
+
+        Ok(Self::load(&path)
+            .map_err(|err| log::warn!("failed to load config from path {}: {err}", path.display()))
+            .unwrap_or_default())
+    }
+
+    /// `$XDG_CONFIG_HOME/runi/config.toml`
+    pub fn default_path() -> Option<PathBuf> {
+        let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+        };
+
+        Some(config_dir.join("runi").join("config.toml"))
+    }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 pub struct Patch {
     #[serde(deserialize_with = "deserialize_exec")]
     pub exec: Exec,
 }
 
+/// Look of the launcher window; colors are `#rrggbb` or `#rrggbbaa`.
+///
+/// Colors left out follow the egui (light or dark) visuals.
+#[derive(Debug, Clone, Deserialize, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    /// Background of the selected item
+    #[serde(deserialize_with = "deserialize_color")]
+    pub selection_color: Color32,
+
+    /// Matched characters of item names
+    #[serde(deserialize_with = "deserialize_color")]
+    pub match_color: Color32,
+
+    /// Fill of the window frame
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    pub background_color: Option<Color32>,
+
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    pub text_color: Option<Color32>,
+
+    /// Window frame and the line under the search field
+    #[serde(deserialize_with = "deserialize_optional_color")]
+    pub border_color: Option<Color32>,
+
+    pub border_width: f32,
+
+    /// Corner radius of the window frame
+    pub radius: f32,
+
+    /// Font family name, resolved with fontconfig
+    pub font_family: Option<String>,
+
+    /// Font size of the search field and item names
+    pub font_size: f32,
+
+    /// Height of the search field
+    pub search_height: f32,
+
+    /// Padding around the search field
+    pub search_padding: f32,
+
+    /// Padding around the item list
+    pub padding: f32,
+
+    /// Whether application icons are shown
+    pub icons: bool,
+
+    /// Icon size in logical pixels
+    pub icon_size: u32,
+
+    /// Icon theme name; defaults to the GTK icon theme
+    pub icon_theme: Option<String>,
+
+    /// Window width in logical pixels
+    #[serde(deserialize_with = "deserialize_size")]
+    pub width: u32,
+
+    /// Window height in logical pixels
+    #[serde(deserialize_with = "deserialize_size")]
+    pub height: u32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            selection_color: Color32::DARK_RED,
+            match_color: Color32::GOLD,
+            background_color: None,
+            text_color: None,
+            border_color: None,
+            border_width: 1.0,
+            radius: 10.0,
+            font_family: None,
+            font_size: 18.0,
+            search_height: 32.0,
+            search_padding: 4.0,
+            padding: 8.0,
+            icons: true,
+            icon_size: 24,
+            icon_theme: None,
+            width: 800,
+            height: 600,
+        }
+    }
+}
+
+fn deserialize_color<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color32, D::Error> {
+    let s = Cow::<'static, str>::deserialize(deserializer)?;
+    Color32::from_hex(&s)
+        .map_err(|err| serde::de::Error::custom(format!("invalid color '{s}': {err:?}")))
+}
+
+fn deserialize_optional_color<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Color32>, D::Error> {
+    deserialize_color(deserializer).map(Some)
+}
+
+/// The window width or height, if it's positive
+pub fn valid_size(size: u32) -> Option<u32> {
+    (size > 0).then_some(size)
+}
+
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    let size = u32::deserialize(deserializer)?;
+    valid_size(size).ok_or_else(|| {
+        serde::de::Error::custom(format!("invalid size {size}: expected a positive number"))
+    })
+}
+
 fn deserialize_exec<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Exec, D::Error> {
     let s = Cow::<'static, str>::deserialize(deserializer)?;
     s.parse().map_err(serde::de::Error::custom)
 }
 
+fn deserialize_terminal<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<TerminalCommand>, D::Error> {
+    let s = Cow::<'static, str>::deserialize(deserializer)?;
+    s.parse().map(Some).map_err(serde::de::Error::custom)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -60,10 +348,138 @@ mod test {
         assert_eq!(
             patch,
             Patch {
-                exec: Exec {
-                    cmd: "alacritty -v".to_owned(),
-                }
+                exec: "alacritty -v".parse().unwrap(),
+            }
+        );
+        assert_eq!(config.theme, Theme::default());
+        assert!(!config.show_filtered);
+        assert_eq!(config.terminal, None);
+        assert_eq!(config.second_invocation, SecondInvocation::Toggle);
+        assert_eq!(config.launch, LaunchMethod::Exec);
+        assert_eq!(config.fallbacks, []);
+    }
+
+    #[test]
+    fn test_second_invocation() {
+        let config: Config = toml::from_str("second_invocation = \"focus\"").unwrap();
+        assert_eq!(config.second_invocation, SecondInvocation::Focus);
+
+        assert!(toml::from_str::<Config>("second_invocation = \"ignore\"").is_err());
+    }
+
+    #[test]
+    fn test_launch() {
+        let config: Config =
+            toml::from_str("launch = \"detach\"\nlaunch_log = \"/tmp/runi.log\"").unwrap();
+        assert_eq!(config.launch, LaunchMethod::Detach);
+        assert_eq!(config.launch_log, Some(PathBuf::from("/tmp/runi.log")));
+
+        let config: Config = toml::from_str("launch = \"systemd\"").unwrap();
+        assert_eq!(config.launch, LaunchMethod::Systemd);
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        let config: Config = toml::from_str(
+            r#"
+            [[fallback]]
+            type = "search"
+            name = "DuckDuckGo"
+            url = "https://duckduckgo.com/?q={query}"
+
+            [[fallback]]
+            type = "terminal"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.fallbacks,
+            [
+                Fallback::Search {
+                    name: "DuckDuckGo".to_owned(),
+                    url: "https://duckduckgo.com/?q={query}".to_owned(),
+                },
+                Fallback::Terminal,
+            ]
+        );
+
+        assert_eq!(Config::default().fallbacks, []);
+
+        assert!(toml::from_str::<Config>("[[fallback]]\ntype = \"browser\"").is_err());
+        assert!(toml::from_str::<Config>("[[fallback]]\ntype = \"search\"").is_err());
+    }
+
+    #[test]
+    fn test_shortcuts() {
+        let config: Config = toml::from_str(
+            r#"
+            [[shortcut]]
+            keyword = "gh"
+            name = "GitHub"
+            url = "https://github.com/search?q={query}"
+
+            [[shortcut]]
+            keyword = "man"
+            command = "man {query}"
+            terminal = true
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            config.shortcuts,
+            [
+                Shortcut {
+                    keyword: "gh".to_owned(),
+                    name: Some("GitHub".to_owned()),
+                    target: ShortcutTarget::Url {
+                        url: "https://github.com/search?q={query}".to_owned()
+                    },
+                },
+                Shortcut {
+                    keyword: "man".to_owned(),
+                    name: None,
+                    target: ShortcutTarget::Command {
+                        command: "man {query}".to_owned(),
+                        terminal: true
+                    },
+                },
+            ]
+        );
+
+        assert!(toml::from_str::<Config>("[[shortcut]]\nkeyword = \"gh\"").is_err());
+        let url_in_terminal =
+            "[[shortcut]]\nkeyword = \"gh\"\nurl = \"https://github.com\"\nterminal = true";
+        assert!(toml::from_str::<Config>(url_in_terminal).is_err());
+    }
+
+    #[test]
+    fn test_theme() {
+        let config: Config = toml::from_str(
+            r##"
+            [theme]
+            selection_color = "#285577"
+            background_color = "#222222cc"
+            font_family = "monospace"
+            radius = 0.0
+            width = 640
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(
+            config.theme,
+            Theme {
+                selection_color: Color32::from_rgb(0x28, 0x55, 0x77),
+                background_color: Some(Color32::from_rgba_unmultiplied(0x22, 0x22, 0x22, 0xcc)),
+                font_family: Some("monospace".to_owned()),
+                radius: 0.0,
+                width: 640,
+                ..Default::default()
             }
         );
+
+        assert!(toml::from_str::<Config>("[theme]\ntext_color = \"red\"").is_err());
+        assert!(toml::from_str::<Config>("[theme]\nfont = \"monospace\"").is_err());
+        assert!(toml::from_str::<Config>("[theme]\nwidth = 0").is_err());
     }
 }
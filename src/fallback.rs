@@ -0,0 +1,135 @@
+use std::fmt::Write;
+
+use crate::{
+    config::Fallback,
+    model::{Application, Item, TerminalCommand},
+};
+
+/// Icons of the fallback rows
+pub const ICONS: &[&str] = &["utilities-terminal", "web-browser"];
+
+/// Rows doing something with `query`, offered below the matches.
+///
+/// The terminal row is left out if no terminal emulator is known.
+pub fn items(fallbacks: &[Fallback], query: &str, terminal: Option<&TerminalCommand>) -> Vec<Item> {
+    let query = query.trim();
+    if query.is_empty() {
+        return vec![];
+    }
+
+    fallbacks
+        .iter()
+        .filter_map(|fallback| command(fallback, query, terminal))
+        .map(|app| Item::Command {
+            app: Box::new(app),
+            note: None,
+        })
+        .collect()
+}
+
+fn command(
+    fallback: &Fallback,
+    query: &str,
+    terminal: Option<&TerminalCommand>,
+) -> Option<Application> {
+    let shell = |script: String| vec!["sh".to_owned(), "-c".to_owned(), script];
+
+    let app = match fallback {
+        Fallback::Shell => Application::from_command(
+            format!("Run \"{query}\" in shell"),
+            shell(query.to_owned()),
+            "utilities-terminal",
+        ),
+        Fallback::Terminal => {
+            let mut app = Application::from_command(
+                format!("Run \"{query}\" in terminal"),
+                // keep the terminal open, so the output can be read
+                shell(format!("{query}; exec \"${{SHELL:-sh}}\"")),
+                "utilities-terminal",
+            );
+            app.terminal = true;
+            app.terminal_command = Some(terminal?.clone());
+            app
+        }
+        Fallback::Search { name, url } => Application::from_command(
+            format!("Search \"{query}\" with {name}"),
+            vec!["xdg-open".to_owned(), expand_url(url, query)],
+            "web-browser",
+        ),
+    };
+
+    Some(app)
+}
+
+/// Replaces `{query}` in `template` with the URL-encoded `query`.
+pub fn expand_url(template: &str, query: &str) -> String {
+    template.replace("{query}", &url_encode(query))
+}
+
+/// Percent-encodes everything but the unreserved characters of RFC 3986.
+fn url_encode(s: &str) -> String {
+    let mut encoded = String::with_capacity(s.len());
+
+    for byte in s.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_items() {
+        let fallbacks = [
+            Fallback::Shell,
+            Fallback::Terminal,
+            Fallback::Search {
+                name: "DuckDuckGo".to_owned(),
+                url: "https://duckduckgo.com/?q={query}".to_owned(),
+            },
+        ];
+        let terminal: TerminalCommand = "foot {cmd}".parse().unwrap();
+
+        let items = items(&fallbacks, " ls -l ", Some(&terminal));
+        let names: Vec<_> = items.iter().map(Item::name).collect();
+        assert_eq!(
+            names,
+            [
+                "Run \"ls -l\" in shell",
+                "Run \"ls -l\" in terminal",
+                "Search \"ls -l\" with DuckDuckGo",
+            ]
+        );
+        assert_eq!(items[0].argv(), ["sh", "-c", "ls -l"]);
+        assert_eq!(
+            items[2].argv(),
+            ["xdg-open", "https://duckduckgo.com/?q=ls%20-l"]
+        );
+
+        // no terminal row without a terminal, no rows without a query
+        assert_eq!(super::items(&fallbacks, "ls", None).len(), 2);
+        assert!(super::items(&fallbacks, "  ", Some(&terminal)).is_empty());
+    }
+
+    #[test]
+    fn test_expand_url() {
+        assert_eq!(
+            expand_url("https://example.com/search?q={query}&lang=en", "a+b & ü/c"),
+            "https://example.com/search?q=a%2Bb%20%26%20%C3%BC%2Fc&lang=en"
+        );
+        assert_eq!(
+            expand_url("https://example.com", "x"),
+            "https://example.com"
+        );
+    }
+}
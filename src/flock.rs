@@ -1,7 +1,9 @@
 use std::{
     env,
-    fs::{self, File},
-    path::PathBuf,
+    fs::{self, File, OpenOptions},
+    io::{Read, Seek, Write},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
 };
 
 pub struct Lock(Option<LockPriv>);
@@ -24,37 +26,283 @@ impl Drop for Lock {
 
 impl Lock {
     pub fn obtain() -> Option<Self> {
-        let dir = env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_owned());
-        let display = env::var("WAYLAND_DISPLAY")
-            .or_else(|_| env::var("DISPLAY").map(|c| c.replace(':', "x")))
-            .unwrap_or_default();
-
-        let path = PathBuf::from(dir).join(format!("runi-{display}.lock"));
+        Self::obtain_at(runtime_path("lock"))
+    }
 
+    fn obtain_at(path: PathBuf) -> Option<Self> {
         log::debug!("flock file: {}", path.display());
 
-        let file = match File::create(&path) {
-            Ok(f) => f,
-            Err(err) => {
-                log::warn!("failed to create a lock file {}: {err}", path.display());
-                return Some(Self::unlocked());
-            }
-        };
+        // a stale lock is reclaimed once, a second conflict means someone else got it first
+        for reclaimed in [false, true] {
+            let file = match open(&path) {
+                Ok(f) => f,
+                Err(err) => {
+                    log::warn!("failed to create a lock file {}: {err}", path.display());
+                    return Some(Self::unlocked());
+                }
+            };
 
-        if let Err(err) = file.try_lock() {
-            match err {
-                fs::TryLockError::Error(err) => {
+            match file.try_lock() {
+                Ok(()) => {
+                    if let Err(err) = Owner::current().write(&file) {
+                        log::warn!("failed to write a lock file {}: {err}", path.display());
+                    }
+                    return Some(Self(Some(LockPriv { file, path })));
+                }
+                Err(fs::TryLockError::Error(err)) => {
                     log::warn!("failed to lock a file {}: {err}", path.display());
                     return Some(Self::unlocked());
                 }
-                fs::TryLockError::WouldBlock => return None,
+                Err(fs::TryLockError::WouldBlock) => {}
+            }
+
+            // the lock is still held, e.g. by an app that inherited it, while its owner is gone
+            match Owner::read(&file) {
+                Some(owner) if !reclaimed && !owner.is_alive() => {
+                    log::warn!(
+                        "reclaiming the lock {} of exited process {}",
+                        path.display(),
+                        owner.pid
+                    );
+                    // unlinking leaves the old lock to its holder, a new file gets a new lock
+                    if let Err(err) = fs::remove_file(&path) {
+                        log::warn!("failed to remove a lock file {}: {err}", path.display());
+                        return None;
+                    }
+                }
+                _ => return None,
             }
         }
 
-        Some(Self(Some(LockPriv { file, path })))
+        None
     }
 
     pub const fn unlocked() -> Self {
         Self(None)
     }
 }
+
+/// Opens (without truncating, the owner may still be running) or creates the lock file.
+fn open(path: &Path) -> std::io::Result<File> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(path)?;
+
+    // std opens files with O_CLOEXEC already, but launched apps must never hold the lock
+    // SAFETY: the descriptor belongs to `file`
+    let flags = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GETFD) };
+    if flags < 0
+        || unsafe { libc::fcntl(file.as_raw_fd(), libc::F_SETFD, flags | libc::FD_CLOEXEC) } < 0
+    {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(file)
+}
+
+/// Process holding the lock, written to the lock file as `<pid> <start time>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Owner {
+    pid: u32,
+
+    /// Tells the owner apart from a later process with the same PID; `None` if unknown
+    start_time: Option<u64>,
+}
+
+impl Owner {
+    fn current() -> Self {
+        let pid = std::process::id();
+
+        Self {
+            pid,
+            start_time: start_time(pid),
+        }
+    }
+
+    fn read(mut file: &File) -> Option<Self> {
+        let mut buf = String::new();
+        file.rewind().ok()?;
+        file.read_to_string(&mut buf).ok()?;
+
+        buf.parse().ok()
+    }
+
+    fn write(&self, mut file: &File) -> std::io::Result<()> {
+        file.set_len(0)?;
+        file.rewind()?;
+        match self.start_time {
+            Some(start_time) => writeln!(file, "{} {start_time}", self.pid),
+            None => writeln!(file, "{}", self.pid),
+        }
+    }
+
+    /// Whether the owner is still running; assumed when it can't be told.
+    fn is_alive(&self) -> bool {
+        if !Path::new("/proc/self/stat").exists() {
+            return true;
+        }
+
+        match start_time(self.pid) {
+            Some(start_time) => self.start_time.is_none_or(|owner| owner == start_time),
+            None => false,
+        }
+    }
+}
+
+impl std::str::FromStr for Owner {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = s.split_whitespace();
+        let pid = fields.next().unwrap_or_default().parse()?;
+        let start_time = fields.next().map(str::parse).transpose()?;
+
+        Ok(Self { pid, start_time })
+    }
+}
+
+/// Start time of process `pid` in clock ticks since boot, from `/proc/<pid>/stat`
+fn start_time(pid: u32) -> Option<u64> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+    // the command name in parentheses may contain spaces, the 22nd field follows it
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(19)?.parse().ok()
+}
+
+/// `$XDG_RUNTIME_DIR/runi-<display>.<extension>`, one per display the launcher runs on
+pub(crate) fn runtime_path(extension: &str) -> PathBuf {
+    let dir = env::var("XDG_RUNTIME_DIR").unwrap_or_else(|_| "/tmp".to_owned());
+    let display = env::var("WAYLAND_DISPLAY")
+        .or_else(|_| env::var("DISPLAY").map(|c| c.replace(':', "x")))
+        .unwrap_or_default();
+
+    PathBuf::from(dir).join(format!("runi-{display}.{extension}"))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        process::{Command, Stdio},
+        thread,
+        time::{Duration, Instant},
+    };
+
+    use super::*;
+
+    /// Set for the child process of `test_contention`, holding the lock at the given path
+    const HOLDER_ENV: &str = "RUNI_TEST_LOCK_HOLDER";
+
+    fn lock_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("runi-{name}-{}.lock", std::process::id()))
+    }
+
+    #[test]
+    fn test_owner() {
+        let owner = Owner::current();
+        assert!(owner.start_time.is_some());
+        assert!(owner.is_alive());
+
+        assert_eq!(
+            "42 1337\n".parse::<Owner>().unwrap(),
+            Owner {
+                pid: 42,
+                start_time: Some(1337)
+            }
+        );
+        assert_eq!("42".parse::<Owner>().unwrap().start_time, None);
+        assert!("".parse::<Owner>().is_err());
+
+        // same PID, but started at another time
+        let reused = Owner {
+            start_time: owner.start_time.map(|time| time + 1),
+            ..owner
+        };
+        assert!(!reused.is_alive());
+    }
+
+    #[test]
+    fn test_contention() {
+        // the child side, see below
+        if let Some(path) = env::var_os(HOLDER_ENV) {
+            let _lock = Lock::obtain_at(path.into()).unwrap();
+
+            // hold the lock until the parent closes stdin
+            let _ = std::io::stdin().read_line(&mut String::new());
+            return;
+        }
+
+        let path = lock_path("contention");
+        let mut child = Command::new(env::current_exe().unwrap())
+            .args(["flock::test::test_contention", "--exact"])
+            .env(HOLDER_ENV, &path)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        // the child has the lock once it wrote itself into the file
+        let started = Instant::now();
+        while fs::read_to_string(&path)
+            .ok()
+            .and_then(|owner| owner.parse::<Owner>().ok())
+            .is_none_or(|owner| owner.pid != child.id())
+        {
+            assert!(
+                started.elapsed() < Duration::from_secs(10),
+                "the child didn't lock"
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(Lock::obtain_at(path.clone()).is_none());
+
+        drop(child.stdin.take());
+        assert!(child.wait().unwrap().success());
+        assert!(!path.exists());
+
+        let lock = Lock::obtain_at(path.clone()).unwrap();
+        assert!(lock.0.is_some());
+        drop(lock);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_stale_lock() {
+        let path = lock_path("stale");
+        let _ = fs::remove_file(&path);
+
+        // an exited owner whose lock lives on in another descriptor, like one inherited by an app
+        let mut exited = Command::new("true").spawn().unwrap();
+        exited.wait().unwrap();
+        let inherited = open(&path).unwrap();
+        inherited.try_lock().unwrap();
+        writeln!(&inherited, "{} 1", exited.id()).unwrap();
+
+        let lock = Lock::obtain_at(path.clone()).unwrap();
+        assert!(lock.0.is_some());
+        assert_eq!(
+            Owner::read(&lock.0.as_ref().unwrap().file),
+            Some(Owner::current())
+        );
+
+        // a running owner keeps its lock
+        assert!(Lock::obtain_at(path.clone()).is_none());
+
+        drop(lock);
+    }
+
+    #[test]
+    fn test_cloexec() {
+        let path = lock_path("cloexec");
+        let lock = Lock::obtain_at(path).unwrap();
+        let fd = lock.0.as_ref().unwrap().file.as_raw_fd();
+
+        // SAFETY: the descriptor belongs to the lock file
+        let flags = unsafe { libc::fcntl(fd, libc::F_GETFD) };
+        assert_ne!(flags & libc::FD_CLOEXEC, 0);
+    }
+}
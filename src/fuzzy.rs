@@ -0,0 +1,189 @@
+/// Points for every matched character
+const MATCH: i32 = 16;
+/// Bonus for matching the first character of the string
+const PREFIX_BONUS: i32 = 12;
+/// Bonus for matching the first character of a word
+const BOUNDARY_BONUS: i32 = 8;
+/// Bonus for matching an uppercase character following a lowercase one (camelCase)
+const CAMEL_BONUS: i32 = 7;
+/// Bonus for matching right after the previously matched character
+const CONSECUTIVE_BONUS: i32 = 6;
+/// Penalty for skipping characters between two matched ones
+const GAP_OPEN: i32 = 3;
+/// Penalty for every further skipped character
+const GAP_EXTEND: i32 = 1;
+/// Maximum penalty for skipping characters before the first matched one
+const LEADING_GAP_MAX: i32 = 3;
+
+const NONE: i32 = i32::MIN / 2;
+
+/// Lowercases `c`, keeping only the first char of multi-char lowercase forms like `İ`'s
+fn fold(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Match {
+    /// Match quality, from 0.0 to 1.0
+    pub score: f64,
+
+    /// Indices of matched characters (not bytes) in the haystack
+    pub indices: Vec<usize>,
+}
+
+/// Case-insensitively matches `needle` as a subsequence of `haystack`.
+///
+/// Among all alignments the one with the best score is picked: matches at the beginning of
+/// the haystack, at word boundaries and camelCase humps, and runs of consecutive characters are
+/// rewarded, while gaps between matched characters are penalized.
+pub fn fuzzy_match(haystack: &str, needle: &str) -> Option<Match> {
+    // both sides fold every char to a single one, so `indices` line up with the haystack
+    let needle: Vec<char> = needle.chars().map(fold).collect();
+    let chars: Vec<char> = haystack.chars().collect();
+    let lower: Vec<char> = chars.iter().copied().map(fold).collect();
+
+    let (n, m) = (chars.len(), needle.len());
+
+    if m == 0 {
+        return Some(Match {
+            score: 1.0,
+            indices: vec![],
+        });
+    }
+
+    // bail out early if the needle isn't a subsequence at all
+    let mut rest = lower.iter();
+    if !needle.iter().all(|c| rest.any(|h| h == c)) {
+        return None;
+    }
+
+    let bonus: Vec<i32> = (0..n).map(|j| bonus_at(&chars, j)).collect();
+
+    // scores[i * n + j]: best score with needle[i] matched at haystack[j]
+    let mut scores = vec![NONE; m * n];
+    // from[i * n + j]: haystack index of needle[i - 1] in that alignment
+    let mut from = vec![0; m * n];
+
+    for j in 0..n {
+        if lower[j] == needle[0] {
+            scores[j] = MATCH + bonus[j] - (j as i32).min(LEADING_GAP_MAX);
+        }
+    }
+
+    for (i, &c) in needle.iter().enumerate().skip(1) {
+        let (prev, row) = ((i - 1) * n, i * n);
+
+        // best alignment of needle[i - 1] followed by a gap, ending right before j
+        let mut gap = NONE;
+        let mut gap_from = 0;
+
+        for j in i..n {
+            if j >= 2 {
+                let opened = scores[prev + j - 2] - GAP_OPEN;
+                gap -= GAP_EXTEND;
+
+                if opened >= gap {
+                    gap = opened;
+                    gap_from = j - 2;
+                }
+            }
+
+            if lower[j] != c {
+                continue;
+            }
+
+            let consecutive = scores[prev + j - 1] + bonus[j].max(CONSECUTIVE_BONUS);
+            let after_gap = gap + bonus[j];
+
+            let (score, origin) = if consecutive >= after_gap {
+                (consecutive, j - 1)
+            } else {
+                (after_gap, gap_from)
+            };
+
+            if score > NONE / 2 {
+                scores[row + j] = score + MATCH;
+                from[row + j] = origin;
+            }
+        }
+    }
+
+    let last = (m - 1) * n;
+    let (mut j, best) = (0..n)
+        .map(|j| (j, scores[last + j]))
+        .max_by_key(|(_, score)| *score)
+        .filter(|(_, score)| *score > NONE / 2)?;
+
+    let mut indices = vec![0; m];
+    for i in (0..m).rev() {
+        indices[i] = j;
+        j = from[i * n + j];
+    }
+
+    // score of a needle matching the beginning of the haystack in one run
+    let perfect = (MATCH + CONSECUTIVE_BONUS) * m as i32 + PREFIX_BONUS - CONSECUTIVE_BONUS;
+
+    Some(Match {
+        score: (best as f64 / perfect as f64).clamp(0.0, 1.0),
+        indices,
+    })
+}
+
+fn bonus_at(chars: &[char], j: usize) -> i32 {
+    let Some(prev) = j.checked_sub(1).map(|prev| chars[prev]) else {
+        return PREFIX_BONUS;
+    };
+    let current = chars[j];
+
+    if !prev.is_alphanumeric() && current.is_alphanumeric() {
+        BOUNDARY_BONUS
+    } else if prev.is_lowercase() && current.is_uppercase() {
+        CAMEL_BONUS
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn score(haystack: &str, needle: &str) -> f64 {
+        fuzzy_match(haystack, needle).map_or(0.0, |m| m.score)
+    }
+
+    fn indices(haystack: &str, needle: &str) -> Vec<usize> {
+        fuzzy_match(haystack, needle).unwrap().indices
+    }
+
+    #[test]
+    fn test_subsequence() {
+        assert!(fuzzy_match("Firefox", "fx").is_some());
+        assert!(fuzzy_match("Firefox", "xf").is_none());
+        assert!(fuzzy_match("Firefox", "firefox nightly").is_none());
+        assert_eq!(score("Firefox", "firefox"), 1.0);
+        assert_eq!(score("Firefox", ""), 1.0);
+    }
+
+    #[test]
+    fn test_indices() {
+        assert_eq!(indices("Firefox", "fx"), [0, 6]);
+        // prefers word boundaries over the first occurrence
+        assert_eq!(indices("Visual Studio Code", "vsc"), [0, 7, 14]);
+        assert_eq!(indices("GitKraken", "gk"), [0, 3]);
+        // prefers consecutive characters
+        assert_eq!(indices("abcab", "ab"), [0, 1]);
+        // characters with a multi-byte UTF-8 encoding
+        assert_eq!(indices("Przeglądarka WWW", "aw"), [11, 13]);
+        // `İ` lowercases to two chars, folded to one on both sides
+        assert_eq!(indices("İstanbul", "İs"), [0, 1]);
+        assert_eq!(indices("Kİtap", "it"), [1, 2]);
+    }
+
+    #[test]
+    fn test_ranking() {
+        assert!(score("Firefox", "fire") > score("Firefox", "fox"));
+        assert!(score("Visual Studio Code", "vsc") > score("Xviewer Screenshot", "vsc"));
+        assert!(score("GitKraken", "gk") > score("Gnome Disks", "gk"));
+    }
+}
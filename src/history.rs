@@ -0,0 +1,364 @@
+use std::{
+    collections::HashMap,
+    env,
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::model::Application;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Launch history used to rank frequently and recently used applications first.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct History {
+    #[serde(default)]
+    launches: HashMap<String, Launches>,
+
+    /// Launches recorded since the history was last saved
+    #[serde(skip)]
+    unsaved: HashMap<String, Launches>,
+
+    #[serde(skip)]
+    path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Launches {
+    /// Number of launches
+    count: u32,
+
+    /// Time of the last launch (seconds since the Unix epoch)
+    last: u64,
+}
+
+impl History {
+    /// Loads the history from `$XDG_STATE_HOME/runi/history.toml`, starting afresh on failure.
+    pub fn load_default() -> Self {
+        let Some(path) = default_path() else {
+            log::warn!("neither XDG_STATE_HOME nor HOME is set; launch history is disabled");
+            return Self::default();
+        };
+
+        let mut history = if path.exists() {
+            Self::load(&path)
+                .map_err(|err| log::warn!("failed to load history from {}: {err}", path.display()))
+                .unwrap_or_default()
+        } else {
+            Self::default()
+        };
+
+        history.path = Some(path);
+        history
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let buf = std::fs::read_to_string(path)?;
+        let history = toml::from_str(&buf)?;
+
+        Ok(history)
+    }
+
+    /// Merges the launches recorded since the last save into the file.
+    ///
+    /// Other instances (e.g. one-shot `runi` next to a daemon) save to the same file, so it's
+    /// read again under a lock rather than overwritten with what was loaded at startup.
+    pub fn save(&mut self) -> anyhow::Result<()> {
+        if self.path.is_none() {
+            self.unsaved.clear();
+            return Ok(());
+        }
+
+        let unsaved = std::mem::take(&mut self.unsaved);
+        let result = self.update(|launches| {
+            for (id, new) in &unsaved {
+                let launches = launches.entry(id.clone()).or_insert(Launches {
+                    count: 0,
+                    last: new.last,
+                });
+                launches.count = launches.count.saturating_add(new.count);
+                launches.last = launches.last.max(new.last);
+            }
+        });
+        if result.is_err() {
+            self.unsaved = unsaved;
+        }
+
+        result
+    }
+
+    /// Applies `change` to the launches in the file, which are kept in memory afterwards.
+    fn update(
+        &mut self,
+        change: impl FnOnce(&mut HashMap<String, Launches>),
+    ) -> anyhow::Result<()> {
+        let Some(path) = self.path.as_ref() else {
+            change(&mut self.launches);
+            return Ok(());
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+
+        let lock_path = path.with_extension("toml.lock");
+        let lock = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lock_path)
+            .with_context(|| format!("failed to open {}", lock_path.display()))?;
+        lock.lock()
+            .with_context(|| format!("failed to lock {}", lock_path.display()))?;
+
+        let mut launches = if path.exists() {
+            Self::load(path)?.launches
+        } else {
+            HashMap::new()
+        };
+        change(&mut launches);
+
+        // write to a temporary file first, so a crash never leaves a truncated history; the PID
+        // keeps instances launching apps at the same time from writing into each other's file
+        let tmp = path.with_extension(format!("toml.{}.tmp", std::process::id()));
+        let file = Self {
+            launches,
+            ..Default::default()
+        };
+        std::fs::write(&tmp, toml::to_string(&file)?)?;
+        std::fs::rename(&tmp, path)?;
+
+        self.launches = file.launches;
+        Ok(())
+    }
+
+    pub fn record(&mut self, app: &Application, now: u64) {
+        for launches in [&mut self.launches, &mut self.unsaved] {
+            let launches = launches.entry(app.id.clone()).or_insert(Launches {
+                count: 0,
+                last: now,
+            });
+
+            launches.count = launches.count.saturating_add(1);
+            launches.last = now;
+        }
+    }
+
+    /// Records a launch of `app` which `start` performs, taking it back if that fails.
+    ///
+    /// The launch is saved before `start`, as it may replace the process.
+    pub fn record_launch(
+        &mut self,
+        app: &Application,
+        start: impl FnOnce() -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let previous = self.launches.get(&app.id).copied();
+        let now = now();
+        self.record(app, now);
+        self.save_or_warn();
+
+        let result = start();
+        if result.is_err() {
+            if self.unsaved.contains_key(&app.id) {
+                // saving failed, the launch never left this process
+                forget(&mut self.unsaved, &app.id, now, None);
+                forget(&mut self.launches, &app.id, now, previous);
+            } else if let Err(err) =
+                self.update(|launches| forget(launches, &app.id, now, previous))
+            {
+                log::warn!("failed to save launch history: {err}");
+            }
+        }
+
+        result
+    }
+
+    fn save_or_warn(&mut self) {
+        if let Err(err) = self.save() {
+            log::warn!("failed to save launch history: {err}");
+        }
+    }
+
+    /// Launch count weighted by how long ago the app was last launched.
+    pub fn frecency(&self, app: &Application, now: u64) -> f64 {
+        let Some(launches) = self.launches.get(&app.id) else {
+            return 0.0;
+        };
+
+        let age = now.saturating_sub(launches.last);
+        let recency = if age < 4 * DAY {
+            1.0
+        } else if age < 14 * DAY {
+            0.7
+        } else if age < 31 * DAY {
+            0.5
+        } else if age < 90 * DAY {
+            0.3
+        } else {
+            0.1
+        };
+
+        launches.count as f64 * recency
+    }
+}
+
+/// Takes back the launch of `id` recorded at `at`, restoring the last launch time from `previous`.
+fn forget(launches: &mut HashMap<String, Launches>, id: &str, at: u64, previous: Option<Launches>) {
+    let Some(entry) = launches.get_mut(id) else {
+        return;
+    };
+
+    if entry.count <= 1 {
+        launches.remove(id);
+        return;
+    }
+
+    entry.count -= 1;
+    if entry.last == at
+        && let Some(previous) = previous
+    {
+        entry.last = previous.last;
+    }
+}
+
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn default_path() -> Option<PathBuf> {
+    let state_dir = match env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local/state"),
+    };
+
+    Some(state_dir.join("runi").join("history.toml"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn app(name: &str) -> Application {
+        Application::new(
+            name.to_owned(),
+            None,
+            name.to_lowercase().parse().unwrap(),
+            None,
+            None,
+            None,
+            vec![],
+        )
+    }
+
+    #[test]
+    fn test_frecency() {
+        let firefox = app("Firefox");
+        let alacritty = app("Alacritty");
+        let signal = app("Signal");
+
+        let now = 1000 * DAY;
+        let mut history = History::default();
+
+        history.record(&firefox, now - 100 * DAY);
+        history.record(&firefox, now - 100 * DAY);
+        history.record(&firefox, now - 100 * DAY);
+        history.record(&alacritty, now - DAY);
+
+        assert_eq!(history.frecency(&signal, now), 0.0);
+        // launched often, but long ago
+        assert!(history.frecency(&firefox, now) < history.frecency(&alacritty, now));
+
+        history.record(&firefox, now);
+        assert_eq!(history.frecency(&firefox, now), 4.0);
+    }
+
+    #[test]
+    fn test_key() {
+        let mut user = app("Firefox");
+        user.file = Some("/home/user/.local/share/applications/firefox.desktop".into());
+        user.id = "firefox.desktop".to_owned();
+        let mut system = app("Firefox");
+        system.file = Some("/usr/share/applications/firefox.desktop".into());
+        system.id = "firefox.desktop".to_owned();
+
+        let mut history = History::default();
+        history.record(&user, 0);
+
+        // launches stick to the ID when the file shadowing it comes or goes
+        assert_eq!(history.frecency(&system, 0), 1.0);
+    }
+
+    #[test]
+    fn test_record_launch() {
+        let firefox = app("Firefox");
+        let signal = app("Signal");
+
+        let mut history = History::default();
+        history.record(&firefox, now());
+        history.record_launch(&firefox, || Ok(())).unwrap();
+        assert_eq!(history.frecency(&firefox, now()), 2.0);
+
+        // failed launches aren't remembered
+        let fail = || Err(anyhow::anyhow!("no such file"));
+        assert!(history.record_launch(&firefox, fail).is_err());
+        assert!(history.record_launch(&signal, fail).is_err());
+        assert_eq!(history.frecency(&firefox, now()), 2.0);
+        assert_eq!(history.frecency(&signal, now()), 0.0);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = env::temp_dir().join(format!("runi-history-{}.toml", std::process::id()));
+        let firefox = app("Firefox");
+
+        let mut history = History {
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+        history.record(&firefox, 42);
+        history.save().unwrap();
+
+        let loaded = History::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.with_extension("toml.lock")).unwrap();
+
+        assert_eq!(loaded.launches, history.launches);
+    }
+
+    #[test]
+    fn test_merge() {
+        let path = env::temp_dir().join(format!("runi-history-merge-{}.toml", std::process::id()));
+        let firefox = app("Firefox");
+        let signal = app("Signal");
+
+        let open = || History {
+            path: Some(path.clone()),
+            ..Default::default()
+        };
+        let mut daemon = open();
+        let mut oneshot = open();
+
+        daemon.record(&firefox, 10);
+        oneshot.record(&firefox, 20);
+        oneshot.record(&signal, 20);
+        oneshot.save().unwrap();
+        daemon.save().unwrap();
+
+        let loaded = History::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(path.with_extension("toml.lock")).unwrap();
+
+        // neither instance overwrites the launches of the other
+        assert_eq!(loaded.launches["Firefox"], Launches { count: 2, last: 20 });
+        assert_eq!(loaded.launches["Signal"], Launches { count: 1, last: 20 });
+        assert_eq!(daemon.launches, loaded.launches);
+    }
+}
@@ -0,0 +1,414 @@
+use std::{
+    collections::HashSet,
+    env,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use anyhow::{Context, Result};
+use egui::ColorImage;
+use image::ImageFormat;
+use resvg::{tiny_skia, usvg};
+
+use crate::loader;
+
+/// Formats runi can decode, in order of preference
+const EXTENSIONS: [&str; 2] = ["png", "svg"];
+
+const FALLBACK_THEME: &str = "hicolor";
+
+/// Icon lookup following the freedesktop Icon Theme Specification.
+#[derive(Debug)]
+pub struct IconFinder {
+    /// Directories containing icon themes and unthemed icons
+    base_dirs: Vec<PathBuf>,
+
+    /// The selected theme, the themes it inherits from, and hicolor
+    themes: Vec<IconTheme>,
+}
+
+#[derive(Debug)]
+struct IconTheme {
+    name: String,
+    dirs: Vec<ThemeDir>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+struct ThemeDir {
+    path: String,
+    size: u32,
+    scale: u32,
+    kind: DirKind,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum DirKind {
+    Fixed,
+    Scalable { min: u32, max: u32 },
+    Threshold(u32),
+}
+
+impl IconFinder {
+    /// Uses `theme`, falling back to the GTK icon theme setting.
+    pub fn new(theme: Option<&str>) -> Self {
+        let theme = theme.map(str::to_owned).or_else(gtk_icon_theme);
+
+        Self::with_base_dirs(base_dirs(), theme.as_deref().unwrap_or(FALLBACK_THEME))
+    }
+
+    fn with_base_dirs(base_dirs: Vec<PathBuf>, theme: &str) -> Self {
+        let mut finder = Self {
+            base_dirs,
+            themes: vec![],
+        };
+
+        finder.add_theme(theme);
+        finder.add_theme(FALLBACK_THEME);
+        finder
+    }
+
+    /// Adds `name` and, depth first, the themes it inherits from.
+    fn add_theme(&mut self, name: &str) {
+        if self.themes.iter().any(|theme| theme.name == name) {
+            return;
+        }
+
+        let Some(index) = self
+            .base_dirs
+            .iter()
+            .map(|dir| dir.join(name).join("index.theme"))
+            .find(|index| index.exists())
+        else {
+            log::debug!("icon theme {name} not found");
+            return;
+        };
+
+        let (theme, parents) = match IconTheme::load(name, &index) {
+            Ok(loaded) => loaded,
+            Err(err) => {
+                log::warn!("failed to load icon theme {}: {err}", index.display());
+                return;
+            }
+        };
+
+        self.themes.push(theme);
+        for parent in parents {
+            self.add_theme(&parent);
+        }
+    }
+
+    /// Finds the file of `icon`, which is either a name or an absolute path.
+    pub fn find(&self, icon: &str, size: u32, scale: u32) -> Option<PathBuf> {
+        let path = Path::new(icon);
+        if path.is_absolute() {
+            return path.exists().then(|| path.to_path_buf());
+        }
+
+        // some entries name the file instead of the icon
+        let name = EXTENSIONS
+            .iter()
+            .find_map(|ext| icon.strip_suffix(&format!(".{ext}")))
+            .unwrap_or(icon);
+
+        self.themes
+            .iter()
+            .find_map(|theme| self.lookup(theme, name, size, scale))
+            .or_else(|| self.lookup_fallback(name))
+    }
+
+    fn lookup(&self, theme: &IconTheme, name: &str, size: u32, scale: u32) -> Option<PathBuf> {
+        let mut closest = None;
+        let mut min_distance = u32::MAX;
+
+        for dir in &theme.dirs {
+            let matches = dir.matches(size, scale);
+            let distance = dir.distance(size, scale);
+            if !matches && distance >= min_distance {
+                continue;
+            }
+
+            let subdir = format!("{}/{}", theme.name, dir.path);
+            let Some(file) = self.files(&subdir, name).next() else {
+                continue;
+            };
+
+            if matches {
+                return Some(file);
+            }
+
+            closest = Some(file);
+            min_distance = distance;
+        }
+
+        closest
+    }
+
+    fn lookup_fallback(&self, name: &str) -> Option<PathBuf> {
+        self.files("", name).next()
+    }
+
+    /// Existing `<base dir>/<subdir>/<name>.<ext>` files.
+    fn files<'a>(&'a self, subdir: &'a str, name: &'a str) -> impl Iterator<Item = PathBuf> + 'a {
+        self.base_dirs.iter().flat_map(move |base_dir| {
+            EXTENSIONS
+                .iter()
+                .map(move |ext| base_dir.join(subdir).join(format!("{name}.{ext}")))
+                .filter(|file| file.exists())
+        })
+    }
+}
+
+impl IconTheme {
+    /// Parses `index.theme`, returning the theme and the names of its parents.
+    fn load(name: &str, index: &Path) -> Result<(Self, Vec<String>)> {
+        let entry = freedesktop_entry_parser::parse_entry(index)?;
+        let section = entry.section("Icon Theme");
+
+        let list = |key| {
+            section
+                .attr(key)
+                .map(|value| {
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|value| !value.is_empty())
+                        .map(str::to_owned)
+                        .collect()
+                })
+                .unwrap_or_else(Vec::new)
+        };
+
+        let mut dirs = vec![];
+        for path in list("Directories")
+            .into_iter()
+            .chain(list("ScaledDirectories"))
+        {
+            let section = entry.section(&path);
+            let number = |key| section.attr(key).and_then(|value| value.parse().ok());
+
+            let Some(size) = number("Size") else {
+                log::debug!("{}: directory {path} has no size", index.display());
+                continue;
+            };
+
+            let kind = match section.attr("Type") {
+                Some("Fixed") => DirKind::Fixed,
+                Some("Scalable") => DirKind::Scalable {
+                    min: number("MinSize").unwrap_or(size),
+                    max: number("MaxSize").unwrap_or(size),
+                },
+                _ => DirKind::Threshold(number("Threshold").unwrap_or(2)),
+            };
+
+            dirs.push(ThemeDir {
+                size,
+                scale: number("Scale").unwrap_or(1),
+                kind,
+                path,
+            });
+        }
+
+        let theme = Self {
+            name: name.to_owned(),
+            dirs,
+        };
+
+        Ok((theme, list("Inherits")))
+    }
+}
+
+impl ThemeDir {
+    fn matches(&self, size: u32, scale: u32) -> bool {
+        if self.scale != scale {
+            return false;
+        }
+
+        match self.kind {
+            DirKind::Fixed => self.size == size,
+            DirKind::Scalable { min, max } => (min..=max).contains(&size),
+            DirKind::Threshold(threshold) => {
+                (self.size.saturating_sub(threshold)..=self.size + threshold).contains(&size)
+            }
+        }
+    }
+
+    fn distance(&self, size: u32, scale: u32) -> u32 {
+        let (min, max) = match self.kind {
+            DirKind::Fixed => (self.size, self.size),
+            DirKind::Scalable { min, max } => (min, max),
+            DirKind::Threshold(threshold) => {
+                (self.size.saturating_sub(threshold), self.size + threshold)
+            }
+        };
+
+        let wanted = size * scale;
+        if wanted < min * self.scale {
+            min * self.scale - wanted
+        } else {
+            wanted.saturating_sub(max * self.scale)
+        }
+    }
+}
+
+/// Decodes a PNG or SVG icon, fitting it into `size`×`size` pixels.
+pub fn decode(path: &Path, size: u32) -> Result<ColorImage> {
+    let data = std::fs::read(path)?;
+
+    if path.extension().is_some_and(|ext| ext == "svg") {
+        let tree = usvg::Tree::from_data(&data, &usvg::Options::default())?;
+        let mut pixmap = tiny_skia::Pixmap::new(size, size).context("invalid icon size")?;
+
+        let svg_size = tree.size();
+        let zoom = size as f32 / svg_size.width().max(svg_size.height());
+        let transform = tiny_skia::Transform::from_scale(zoom, zoom).post_translate(
+            (size as f32 - svg_size.width() * zoom) / 2.0,
+            (size as f32 - svg_size.height() * zoom) / 2.0,
+        );
+        resvg::render(&tree, transform, &mut pixmap.as_mut());
+
+        let size = [pixmap.width() as usize, pixmap.height() as usize];
+        Ok(ColorImage::from_rgba_premultiplied(size, pixmap.data()))
+    } else {
+        let mut image = image::load_from_memory_with_format(&data, ImageFormat::Png)?;
+        if image.width() > size || image.height() > size {
+            image = image.thumbnail(size, size);
+        }
+
+        let size = [image.width() as usize, image.height() as usize];
+        Ok(ColorImage::from_rgba_unmultiplied(
+            size,
+            image.to_rgba8().as_raw(),
+        ))
+    }
+}
+
+/// Looks up and decodes `icons` on a separate thread, sending each one as soon as it's ready.
+///
+/// `size` is in logical pixels; `on_loaded` is called after every sent icon.
+pub fn load_icons(
+    icons: HashSet<String>,
+    theme: Option<String>,
+    size: u32,
+    scale: u32,
+    on_loaded: impl Fn() + Send + 'static,
+) -> Receiver<(String, ColorImage)> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let finder = IconFinder::new(theme.as_deref());
+
+        for icon in icons {
+            let Some(path) = finder.find(&icon, size, scale) else {
+                log::debug!("icon {icon} not found");
+                continue;
+            };
+
+            match decode(&path, size * scale) {
+                Ok(image) => {
+                    if sender.send((icon, image)).is_err() {
+                        return;
+                    }
+                    on_loaded();
+                }
+                Err(err) => log::warn!("failed to load icon {}: {err}", path.display()),
+            }
+        }
+    });
+
+    receiver
+}
+
+/// `~/.icons`, `icons` in XDG data dirs and `/usr/share/pixmaps`
+fn base_dirs() -> Vec<PathBuf> {
+    let loader::AppDirs { system, user } = loader::app_dirs();
+
+    let home = env::var_os("HOME").map(|home| PathBuf::from(home).join(".icons"));
+    let data_dirs = user.into_iter().chain(system).map(|dir| dir.join("icons"));
+
+    home.into_iter()
+        .chain(data_dirs)
+        .chain([PathBuf::from("/usr/share/pixmaps")])
+        .collect()
+}
+
+/// `gtk-icon-theme-name` from `$XDG_CONFIG_HOME/gtk-3.0/settings.ini`
+fn gtk_icon_theme() -> Option<String> {
+    let config_dir = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+
+    let settings = std::fs::read_to_string(config_dir.join("gtk-3.0/settings.ini")).ok()?;
+    settings.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "gtk-icon-theme-name").then(|| value.trim().trim_matches('"').to_owned())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn test_dir(dir: &str) -> PathBuf {
+        PathBuf::from(format!("{}/test/{dir}", env!("CARGO_MANIFEST_DIR")))
+    }
+
+    fn finder() -> IconFinder {
+        IconFinder::with_base_dirs(vec![test_dir("icons"), test_dir("pixmaps")], "Test")
+    }
+
+    fn find(icon: &str, size: u32) -> String {
+        let path = finder().find(icon, size, 1).unwrap();
+        let path = path.strip_prefix(test_dir("")).unwrap();
+
+        path.display().to_string()
+    }
+
+    #[test]
+    fn test_themes() {
+        let themes: Vec<_> = finder().themes.into_iter().map(|t| t.name).collect();
+        assert_eq!(themes, ["Test", "Parent", "hicolor"]);
+    }
+
+    #[test]
+    fn test_find() {
+        // the selected theme comes before hicolor
+        assert_eq!(
+            find("runi-test", 24),
+            "icons/Test/scalable/apps/runi-test.svg"
+        );
+        // inherited themes, then the closest size
+        assert_eq!(
+            find("runi-parent", 16),
+            "icons/Parent/48x48/apps/runi-parent.png"
+        );
+        assert_eq!(
+            find("runi-sized", 16),
+            "icons/hicolor/16x16/apps/runi-sized.png"
+        );
+        assert_eq!(
+            find("runi-sized", 40),
+            "icons/hicolor/48x48/apps/runi-sized.png"
+        );
+        // unthemed icons
+        assert_eq!(find("runi-legacy", 24), "pixmaps/runi-legacy.png");
+        assert_eq!(find("runi-legacy.png", 24), "pixmaps/runi-legacy.png");
+
+        let absolute = test_dir("pixmaps/runi-legacy.png");
+        assert_eq!(
+            finder().find(absolute.to_str().unwrap(), 24, 1),
+            Some(absolute)
+        );
+        assert_eq!(finder().find("runi-missing", 24, 1), None);
+    }
+
+    #[test]
+    fn test_decode() {
+        let svg = decode(&test_dir("icons/Test/scalable/apps/runi-test.svg"), 32).unwrap();
+        assert_eq!(svg.size, [32, 32]);
+
+        let png = decode(&test_dir("pixmaps/runi-legacy.png"), 32).unwrap();
+        assert_eq!(png.size, [2, 2]);
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    fmt,
+    io::{BufRead, BufReader, Write},
+    os::unix::net::{UnixListener, UnixStream},
+    path::{Path, PathBuf},
+    str::FromStr,
+    thread,
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+
+use crate::flock;
+
+/// How long a client waits for the running instance to answer
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// What a client asks the running instance to do, sent as a single line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Request {
+    /// Show the window, or focus it if it's already shown
+    Show,
+
+    /// Show the window, or hide it if it's already shown
+    Toggle,
+}
+
+impl fmt::Display for Request {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Show => "show",
+            Self::Toggle => "toggle",
+        })
+    }
+}
+
+impl FromStr for Request {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "show" => Self::Show,
+            "toggle" => Self::Toggle,
+            _ => anyhow::bail!("unknown request '{s}'"),
+        })
+    }
+}
+
+/// Listening socket of the running instance, removed when dropped
+pub struct Server {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+impl Server {
+    /// Listens on `$XDG_RUNTIME_DIR/runi-<display>.sock`, next to the lock file.
+    pub fn bind() -> Result<Self> {
+        Self::bind_at(&socket_path())
+    }
+
+    fn bind_at(path: &Path) -> Result<Self> {
+        // a socket nobody answers on was left behind by an instance that didn't exit cleanly
+        if path.exists() {
+            anyhow::ensure!(
+                UnixStream::connect(path).is_err(),
+                "another instance is already listening on {}",
+                path.display()
+            );
+            std::fs::remove_file(path)
+                .with_context(|| format!("failed to remove stale socket {}", path.display()))?;
+        }
+
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("failed to listen on {}", path.display()))?;
+        log::debug!("listening on {}", path.display());
+
+        Ok(Self {
+            listener,
+            path: path.to_owned(),
+        })
+    }
+
+    /// Handles clients on a background thread, passing their requests to `on_request`.
+    pub fn serve(&self, mut on_request: impl FnMut(Request) + Send + 'static) -> Result<()> {
+        let listener = self.listener.try_clone()?;
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let result = stream
+                    .map_err(anyhow::Error::from)
+                    .and_then(|stream| handle_client(stream, &mut on_request));
+
+                if let Err(err) = result {
+                    log::warn!("failed to handle a client: {err:#}");
+                }
+            }
+        });
+
+        Ok(())
+    }
+}
+
+fn handle_client(mut stream: UnixStream, on_request: &mut impl FnMut(Request)) -> Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+
+    match line.trim_end().parse() {
+        Ok(request) => {
+            log::debug!("received request: {request}");
+            on_request(request);
+            writeln!(stream, "ok")?;
+        }
+        Err(err) => writeln!(stream, "error: {err}")?,
+    }
+
+    Ok(())
+}
+
+/// Sends `request` to the running instance (or daemon), failing if there's none.
+pub fn send(request: Request) -> Result<()> {
+    send_to(&socket_path(), request)
+}
+
+fn send_to(path: &Path, request: Request) -> Result<()> {
+    let mut stream = UnixStream::connect(path)
+        .with_context(|| format!("failed to connect to {}", path.display()))?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    writeln!(stream, "{request}")?;
+
+    let mut reply = String::new();
+    BufReader::new(&stream).read_line(&mut reply)?;
+
+    match reply.trim_end() {
+        "ok" => Ok(()),
+        "" => anyhow::bail!("the running instance closed the connection"),
+        reply => anyhow::bail!("the running instance replied with '{reply}'"),
+    }
+}
+
+fn socket_path() -> PathBuf {
+    flock::runtime_path("sock")
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::mpsc;
+
+    use super::*;
+
+    #[test]
+    fn test_requests() {
+        let path = std::env::temp_dir().join(format!("runi-ipc-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        // nobody is listening yet
+        assert!(send_to(&path, Request::Show).is_err());
+
+        let server = Server::bind_at(&path).unwrap();
+        assert!(Server::bind_at(&path).is_err());
+
+        let (sender, receiver) = mpsc::channel();
+        server
+            .serve(move |request| sender.send(request).unwrap())
+            .unwrap();
+
+        send_to(&path, Request::Show).unwrap();
+        send_to(&path, Request::Toggle).unwrap();
+        assert_eq!(receiver.recv().unwrap(), Request::Show);
+        assert_eq!(receiver.recv().unwrap(), Request::Toggle);
+
+        let mut stream = UnixStream::connect(&path).unwrap();
+        writeln!(stream, "explode").unwrap();
+        let mut reply = String::new();
+        BufReader::new(&stream).read_line(&mut reply).unwrap();
+        assert_eq!(reply, "error: unknown request 'explode'\n");
+
+        drop(server);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_stale_socket() {
+        let path = std::env::temp_dir().join(format!("runi-stale-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let server = Server::bind_at(&path).unwrap();
+        drop(server);
+        assert!(!path.exists());
+    }
+}
@@ -1,49 +1,130 @@
-use std::{collections::HashSet, env, path::PathBuf, time::Instant};
+use std::{
+    collections::HashSet,
+    env, fs,
+    io::{self, BufRead},
+    path::{Path, PathBuf},
+    time::Instant,
+};
 use walkdir::WalkDir;
 
-use crate::{config::Config, model::Application};
+use crate::{
+    cache::AppCache,
+    config::Config,
+    locale::Locale,
+    model::{Application, FieldCodes, Item, TerminalCommand},
+    session::{self, Session},
+};
 
-pub fn load_apps() -> Vec<Application> {
-    let timer = Instant::now();
-    let AppDirs { system, user, home } = app_dirs();
-
-    let mut config = home
-        .and_then(|mut cfg_dir| {
-            cfg_dir.push(".config");
-            cfg_dir.push("runi");
-            cfg_dir.push("config.toml");
-
-            Config::load(&cfg_dir)
-                .map_err(|err| {
-                    log::warn!(
-                        "failed to load config from path {}: {err}",
-                        cfg_dir.display()
-                    )
-                })
-                .ok()
-        })
-        .unwrap_or_default();
+/// Reads dmenu items: one per non-empty line of stdin.
+pub fn load_lines() -> Vec<Item> {
+    let mut items = vec![];
+
+    for line in io::stdin().lock().lines() {
+        match line {
+            Ok(line) if !line.is_empty() => items.push(Item::Line(line)),
+            Ok(_) => {}
+            Err(err) => {
+                log::warn!("failed to read stdin: {err}");
+                break;
+            }
+        }
+    }
 
-    let mut set: HashSet<Application> = HashSet::new();
+    log::info!("read {} lines from stdin", items.len());
 
-    for dir in system {
-        let apps = process_dir(dir, &mut config);
+    items
+}
 
-        set.extend(apps);
-    }
+/// Loads the executables in `PATH` for run mode.
+pub fn load_executables() -> Vec<Application> {
+    load_executables_from(Session::from_env().path())
+}
 
-    if let Some(user) = user {
-        let user_apps = process_dir(user, &mut config);
+/// Loads executables from `dirs`, the first one of each name winning like in a shell.
+fn load_executables_from(dirs: &[PathBuf]) -> Vec<Application> {
+    let timer = Instant::now();
+    let mut names = HashSet::new();
+    let mut executables = vec![];
+
+    for dir in dirs {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                log::debug!("skipping {}: {err}", dir.display());
+                continue;
+            }
+        };
 
-        for app in user_apps {
-            if let Some(system) = set.replace(app) {
-                log::info!("overriden {}", system.name);
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if names.contains(&entry.file_name()) || !session::is_executable(&path) {
+                continue;
+            }
+
+            if let Some(app) = Application::from_executable(path) {
+                names.insert(entry.file_name());
+                executables.push(app);
             }
         }
     }
 
-    let mut apps: Vec<_> = set.into_iter().collect();
-    apps.sort_unstable_by(|l, r| l.name.cmp(&r.name));
+    executables.sort_unstable_by(|l, r| l.name.cmp(&r.name));
+
+    log::info!(
+        "loaded {} executables in {}ms",
+        executables.len(),
+        timer.elapsed().as_millis()
+    );
+
+    executables
+}
+
+/// Loads apps, parsing only the desktop files changed since the cache was saved.
+pub fn load_apps(config: &Config, rebuild_cache: bool) -> Vec<Application> {
+    let AppDirs { system, user } = app_dirs();
+    let locale = Locale::from_env();
+    let mut cache = AppCache::load_default(locale.as_ref(), rebuild_cache);
+
+    // user entries override system ones, which follow XDG_DATA_DIRS order
+    let dirs = user.into_iter().chain(system);
+    let apps = load_apps_from(dirs, config, locale.as_ref(), &mut cache);
+
+    if let Err(err) = cache.save() {
+        log::warn!("failed to save app cache: {err}");
+    }
+
+    apps
+}
+
+/// Loads apps from data dirs given in order of precedence.
+fn load_apps_from(
+    dirs: impl IntoIterator<Item = PathBuf>,
+    config: &Config,
+    locale: Option<&Locale>,
+    cache: &mut AppCache,
+) -> Vec<Application> {
+    let timer = Instant::now();
+    let session = Session::from_env();
+
+    // desktop file IDs found so far, including hidden entries, which shadow the ones in
+    // dirs of lower precedence as well
+    let mut ids = HashSet::new();
+    let mut apps = vec![];
+
+    for dir in dirs {
+        apps.extend(process_dir(dir, config, locale, &session, &mut ids, cache));
+    }
+
+    apps.sort_unstable_by(|l, r| l.name.cmp(&r.name).then_with(|| l.id.cmp(&r.id)));
+
+    if apps.iter().any(|app| app.terminal) {
+        let terminal = terminal_command(config, &apps);
+        log::info!("terminal emulator: {terminal:?}");
+
+        for app in apps.iter_mut().filter(|app| app.terminal) {
+            app.terminal_command = terminal.clone();
+        }
+    }
 
     log::info!(
         "loaded {} apps in {}ms",
@@ -54,12 +135,19 @@ pub fn load_apps() -> Vec<Application> {
     apps
 }
 
-fn process_dir(dir: PathBuf, cfg: &mut Config) -> Vec<Application> {
+fn process_dir(
+    dir: PathBuf,
+    cfg: &Config,
+    locale: Option<&Locale>,
+    session: &Session,
+    ids: &mut HashSet<String>,
+    cache: &mut AppCache,
+) -> Vec<Application> {
     let dir = dir.join("applications");
 
     log::info!("processing dir: {}", dir.display());
 
-    let walkdir = WalkDir::new(dir);
+    let walkdir = WalkDir::new(&dir);
 
     let mut apps = vec![];
     for file in walkdir.into_iter() {
@@ -72,17 +160,39 @@ fn process_dir(dir: PathBuf, cfg: &mut Config) -> Vec<Application> {
 
         log::debug!("processing file: {}", file.display());
 
-        let mut app = match Application::from_freedesktop_file(file) {
+        let Some(id) = desktop_file_id(&dir, file) else {
+            continue;
+        };
+        if ids.contains(&id) {
+            log::debug!("{id} is overridden, skipping {}", file.display());
+            continue;
+        }
+
+        // a broken file leaves the ID to the next one, a hidden one masks it
+        let mut app = match cache.parse(file, locale) {
             Ok(Some(app)) => app,
-            Ok(None) => continue,
+            Ok(None) => {
+                ids.insert(id);
+                continue;
+            }
             Err(err) => {
                 log::warn!("Failed to parse path: {:?}, err: {err}", file.display());
                 continue;
             }
         };
+        ids.insert(id.clone());
+
+        app.id = id;
+        app.filtered = app.filter_reason(session);
+        if let Some(reason) = &app.filtered
+            && !cfg.show_filtered
+        {
+            log::debug!("hiding {}: {reason}", file.display());
+            continue;
+        }
 
-        if let Some(patch) = cfg.patches.remove(file) {
-            app.exec = patch.exec;
+        if let Some(patch) = cfg.patches.get(file) {
+            app.exec = patch.exec.clone();
         }
 
         apps.push(app);
@@ -91,13 +201,57 @@ fn process_dir(dir: PathBuf, cfg: &mut Config) -> Vec<Application> {
     apps
 }
 
-struct AppDirs {
-    system: Vec<PathBuf>,
-    user: Option<PathBuf>,
-    home: Option<PathBuf>,
+/// Path of `file` relative to `applications_dir`, with `/` replaced by `-`.
+fn desktop_file_id(applications_dir: &Path, file: &Path) -> Option<String> {
+    let relative = file.strip_prefix(applications_dir).ok()?.to_str()?;
+
+    Some(relative.replace('/', "-"))
+}
+
+/// The configured terminal emulator, `$TERMINAL`, or the first installed one.
+fn terminal_command(config: &Config, apps: &[Application]) -> Option<TerminalCommand> {
+    if let Some(terminal) = configured_terminal(config) {
+        return Some(terminal);
+    }
+
+    apps.iter()
+        .filter(|app| app.filtered.is_none() && !app.terminal)
+        .filter(|app| app.categories.iter().any(|x| x == "TerminalEmulator"))
+        .find_map(|app| {
+            let argv = app.exec.expand(&FieldCodes {
+                name: &app.name,
+                icon: None,
+                file: None,
+            });
+
+            TerminalCommand::for_terminal(argv.into_iter().take(1).collect())
+        })
 }
 
-fn app_dirs() -> AppDirs {
+/// The configured terminal emulator or `$TERMINAL`, known without loading apps.
+pub fn configured_terminal(config: &Config) -> Option<TerminalCommand> {
+    if let Some(terminal) = &config.terminal {
+        return Some(terminal.clone());
+    }
+
+    let terminal = env::var("TERMINAL")
+        .ok()
+        .filter(|terminal| !terminal.is_empty())?;
+    match shell_words::split(&terminal) {
+        Ok(args) => TerminalCommand::for_terminal(args),
+        Err(err) => {
+            log::warn!("invalid TERMINAL '{terminal}': {err}");
+            None
+        }
+    }
+}
+
+pub(crate) struct AppDirs {
+    pub system: Vec<PathBuf>,
+    pub user: Option<PathBuf>,
+}
+
+pub(crate) fn app_dirs() -> AppDirs {
     let system = if let Ok(xdg_data_dirs) = env::var("XDG_DATA_DIRS") {
         xdg_data_dirs.split(':').map(PathBuf::from).collect()
     } else {
@@ -107,22 +261,82 @@ fn app_dirs() -> AppDirs {
         ]
     };
 
-    let mut home_dir = None;
-
     let user = if let Ok(xdg_data_home) = env::var("XDG_DATA_HOME") {
         Some(PathBuf::from(xdg_data_home))
     } else if let Ok(home) = env::var("HOME") {
         let mut dir = PathBuf::from(home);
-        home_dir = Some(dir.clone());
         dir.push(".local/share");
         Some(dir)
     } else {
         None
     };
 
-    AppDirs {
-        system,
-        user,
-        home: home_dir,
+    AppDirs { system, user }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_desktop_file_id() {
+        let dir = Path::new("/usr/share/applications");
+
+        assert_eq!(
+            desktop_file_id(dir, &dir.join("firefox.desktop")).as_deref(),
+            Some("firefox.desktop")
+        );
+        assert_eq!(
+            desktop_file_id(dir, &dir.join("kde4/dolphin.desktop")).as_deref(),
+            Some("kde4-dolphin.desktop")
+        );
+    }
+
+    #[test]
+    fn test_executables() {
+        let dir = |name| PathBuf::from(format!("{}/test/bin/{name}", env!("CARGO_MANIFEST_DIR")));
+        let executables = load_executables_from(&[dir("first"), dir("missing"), dir("second")]);
+
+        let executables: Vec<_> = executables
+            .iter()
+            .map(|app| (app.name.as_str(), app.file.clone().unwrap()))
+            .collect();
+
+        assert_eq!(
+            executables,
+            [
+                // shadowed by the earlier dir
+                ("runi-tool", dir("first").join("runi-tool")),
+                ("runi-util", dir("second").join("runi-util")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_precedence() {
+        let dir = |name| PathBuf::from(format!("{}/test/xdg/{name}", env!("CARGO_MANIFEST_DIR")));
+        let apps = load_apps_from(
+            [dir("user"), dir("local"), dir("system")],
+            &Config::default(),
+            None,
+            &mut AppCache::default(),
+        );
+
+        let apps: Vec<_> = apps
+            .iter()
+            .map(|app| (app.id.as_str(), app.name.as_str()))
+            .collect();
+
+        assert_eq!(
+            apps,
+            [
+                // same name, different IDs
+                ("runi-editor.desktop", "Editor"),
+                ("vendor-runi-editor.desktop", "Editor"),
+                // the user file is broken
+                ("runi-player.desktop", "Player (local)"),
+                ("runi-viewer.desktop", "Viewer (user)"),
+            ]
+        );
     }
 }
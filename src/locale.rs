@@ -0,0 +1,109 @@
+use std::{env, fmt::Display, str::FromStr};
+
+/// Messages locale used to pick localized keys such as `Name[de_DE]`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Locale {
+    lang: String,
+    country: Option<String>,
+    modifier: Option<String>,
+}
+
+impl Locale {
+    /// Reads the locale from `LC_ALL`, `LC_MESSAGES` or `LANG`, whichever is set first.
+    pub fn from_env() -> Option<Self> {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .find_map(|var| env::var(var).ok().filter(|value| !value.is_empty()))
+            .and_then(|value| value.parse().ok())
+    }
+
+    /// Locale keys to look up, in the matching order defined by the Desktop Entry spec.
+    pub fn candidates(&self) -> Vec<String> {
+        let Self {
+            lang,
+            country,
+            modifier,
+        } = self;
+
+        let mut candidates = Vec::with_capacity(4);
+
+        if let (Some(country), Some(modifier)) = (country, modifier) {
+            candidates.push(format!("{lang}_{country}@{modifier}"));
+        }
+        if let Some(country) = country {
+            candidates.push(format!("{lang}_{country}"));
+        }
+        if let Some(modifier) = modifier {
+            candidates.push(format!("{lang}@{modifier}"));
+        }
+        candidates.push(lang.clone());
+
+        candidates
+    }
+}
+
+impl Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.lang)?;
+        if let Some(country) = &self.country {
+            write!(f, "_{country}")?;
+        }
+        if let Some(modifier) = &self.modifier {
+            write!(f, "@{modifier}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Locale {
+    type Err = anyhow::Error;
+
+    /// Parses `lang_COUNTRY.ENCODING@MODIFIER`; the encoding is ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (rest, modifier) = match s.split_once('@') {
+            Some((rest, modifier)) => (rest, Some(modifier.to_owned())),
+            None => (s, None),
+        };
+        let rest = rest.split_once('.').map_or(rest, |(rest, _encoding)| rest);
+        let (lang, country) = match rest.split_once('_') {
+            Some((lang, country)) => (lang, Some(country.to_owned())),
+            None => (rest, None),
+        };
+
+        anyhow::ensure!(
+            !lang.is_empty() && lang != "C" && lang != "POSIX",
+            "Locale {s} has no translations"
+        );
+
+        Ok(Self {
+            lang: lang.to_owned(),
+            country,
+            modifier,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_candidates() {
+        let locale: Locale = "sr_RS.UTF-8@latin".parse().unwrap();
+        assert_eq!(
+            locale.candidates(),
+            ["sr_RS@latin", "sr_RS", "sr@latin", "sr"]
+        );
+
+        let locale: Locale = "pl_PL.UTF-8".parse().unwrap();
+        assert_eq!(locale.candidates(), ["pl_PL", "pl"]);
+        assert_eq!(locale.to_string(), "pl_PL");
+
+        let locale: Locale = "de".parse().unwrap();
+        assert_eq!(locale.candidates(), ["de"]);
+
+        assert!("C.UTF-8".parse::<Locale>().is_err());
+        assert!("POSIX".parse::<Locale>().is_err());
+    }
+}
@@ -1,16 +1,43 @@
 mod backend;
+mod cache;
+mod calc;
+mod chars;
+mod cli;
+mod fallback;
 mod flock;
+mod fuzzy;
+mod history;
+mod icon;
+mod ipc;
 mod loader;
+mod locale;
+mod session;
+mod shortcut;
+mod systemd;
 mod ui;
 
 pub mod config;
 pub mod model;
 
-use std::thread;
+use std::{
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
+    thread,
+};
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
-use crate::{backend::UiBackend, loader::load_apps};
+use crate::{
+    cli::{Cli, Command},
+    config::{Config, LaunchMethod, SecondInvocation},
+    history::History,
+    ipc::Request,
+    loader::{load_apps, load_executables, load_lines},
+    model::Item,
+    ui::{Mode, UiOptions},
+};
 pub use flock::Lock;
 
 fn main() -> Result<()> {
@@ -19,25 +46,170 @@ fn main() -> Result<()> {
         .parse_default_env()
         .init();
 
-    let backend: UiBackend = std::env::args()
-        .nth(1)
-        .map(|value| value.parse().unwrap())
-        .unwrap_or_default();
+    let cli = match Cli::parse(std::env::args().skip(1)) {
+        Ok(cli) => cli,
+        Err(err) => {
+            eprintln!("error: {err}\n\nFor more information, try '--help'.");
+            std::process::exit(2);
+        }
+    };
+
+    // neither needs the config, so a broken one doesn't get in the way
+    match cli.command {
+        Command::Help => print!("{}", cli::HELP),
+        Command::Version => println!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")),
+        Command::List | Command::Launch(_) | Command::Ui => {
+            let config = Config::load_or_default(cli.config.as_deref())?;
 
+            match cli.command {
+                Command::Launch(name) => launch(&config, &name, cli.rebuild_cache)?,
+                Command::List => list(&config, cli.rebuild_cache),
+                _ => run(cli, config),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn run(cli: Cli, config: Config) {
     log::info!(
         "init, version: {}, selected backend: {:?}",
         env!("CARGO_PKG_VERSION"),
-        backend
+        cli.backend
     );
 
-    let Some(flock) = flock::Lock::obtain() else {
-        log::info!("another instance detected; exiting");
-        return Ok(());
+    // the resident launcher only knows the apps mode and its own look, so a different one runs
+    // on its own instead of being handed over
+    let standalone = cli.mode != Mode::Apps
+        || cli.prompt.is_some()
+        || cli.width.is_some()
+        || cli.height.is_some();
+
+    let options = UiOptions {
+        backend: cli.backend,
+        mode: cli.mode,
+        prompt: cli.prompt,
+        theme: config::Theme {
+            width: cli.width.unwrap_or(config.theme.width),
+            height: cli.height.unwrap_or(config.theme.height),
+            ..config.theme.clone()
+        },
+        launch: config.launch,
+        launch_log: config.launch_log.clone(),
+        fallbacks: config.fallbacks.clone(),
+        shortcuts: config.shortcuts.clone(),
+        terminal: loader::configured_terminal(&config),
     };
 
-    let apps_thread = thread::spawn(load_apps);
+    if let Mode::Dmenu { .. } = options.mode {
+        // scripts may run several pickers at once, so they don't take the lock
+        let lines_thread = thread::spawn(load_lines);
+        ui::run_ui(lines_thread, options, Lock::unlocked(), None);
 
-    ui::run_ui(apps_thread, backend, flock);
+        // a selection exits the process, so getting here means it was cancelled
+        std::process::exit(1);
+    }
 
-    Ok(())
+    if let Mode::Chars { .. } = options.mode {
+        // picking characters doesn't get in the way of launching apps
+        let chars_thread = thread::spawn(|| {
+            chars::load_chars()
+                .into_iter()
+                .map(|c| Item::Char(Box::new(c)))
+                .collect()
+        });
+        ui::run_ui(chars_thread, options, Lock::unlocked(), None);
+        return;
+    }
+
+    if cli.daemon {
+        let server = match ipc::Server::bind() {
+            Ok(server) => server,
+            Err(err) => {
+                log::error!("{err:#}");
+                std::process::exit(1);
+            }
+        };
+
+        // only the first load may ignore the cache
+        let rebuild_cache = AtomicBool::new(cli.rebuild_cache);
+        let load_items = Arc::new(move || {
+            load_apps(&config, rebuild_cache.swap(false, Ordering::Relaxed))
+                .into_iter()
+                .map(|app| Item::App(Box::new(app)))
+                .collect()
+        });
+
+        ui::run_daemon(load_items, options, server);
+        return;
+    }
+
+    let (flock, server) = if standalone {
+        (Lock::unlocked(), None)
+    } else {
+        // a running instance (or daemon) takes over, see `second_invocation` in the config
+        let request = if cli.toggle || config.second_invocation == SecondInvocation::Toggle {
+            Request::Toggle
+        } else {
+            Request::Show
+        };
+        match ipc::send(request) {
+            Ok(()) => return,
+            Err(err) => log::debug!("no running instance to take over, starting: {err:#}"),
+        }
+
+        let Some(flock) = flock::Lock::obtain() else {
+            log::info!("another instance detected, but it doesn't respond; exiting");
+            return;
+        };
+
+        let server = ipc::Server::bind()
+            .map_err(|err| log::warn!("other instances won't be able to reach this one: {err:#}"))
+            .ok();
+
+        (flock, server)
+    };
+
+    let rebuild_cache = cli.rebuild_cache;
+    let mode = options.mode;
+    let apps_thread = thread::spawn(move || {
+        let apps = match mode {
+            Mode::Run => load_executables(),
+            _ => load_apps(&config, rebuild_cache),
+        };
+
+        apps.into_iter()
+            .map(|app| Item::App(Box::new(app)))
+            .collect()
+    });
+
+    ui::run_ui(apps_thread, options, flock, server);
+}
+
+fn list(config: &Config, rebuild_cache: bool) {
+    for app in load_apps(config, rebuild_cache) {
+        let file = app.file.as_ref().map(|file| file.display().to_string());
+        match &app.filtered {
+            Some(reason) => println!("{}\t{}\t{reason}", app.name, file.unwrap_or_default()),
+            None => println!("{}\t{}", app.name, file.unwrap_or_default()),
+        }
+    }
+}
+
+fn launch(config: &Config, name: &str, rebuild_cache: bool) -> Result<()> {
+    let apps = load_apps(config, rebuild_cache);
+
+    let app = apps
+        .iter()
+        .filter_map(|app| Some((app, app.score(name)?.score)))
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(app, _)| app)
+        .with_context(|| format!("no application matches '{name}'"))?;
+
+    History::load_default().record_launch(app, || match config.launch {
+        LaunchMethod::Exec => app.exec(),
+        LaunchMethod::Detach => app.spawn(None, config.launch_log.as_deref()),
+        LaunchMethod::Systemd => app.spawn_in_scope(None, config.launch_log.as_deref()),
+    })
 }
@@ -1,40 +1,171 @@
 use std::{
     fmt::Display,
+    fs::OpenOptions,
     hash::{Hash, Hasher},
     os::unix::process::CommandExt,
-    path::Path,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
     str::FromStr,
+    thread,
 };
 
 use anyhow::{Context, Result};
+use freedesktop_entry_parser::AttrSelector;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Eq)]
+use crate::{
+    chars::Character,
+    fuzzy::{Match, fuzzy_match},
+    locale::Locale,
+    session::Session,
+    systemd,
+};
+
+#[derive(Debug, Clone, Eq, Serialize, Deserialize)]
 pub struct Application {
+    /// Desktop file ID, e.g. `org.gnome.Nautilus.desktop`; identifies the app
+    pub id: String,
     pub name: String,
     pub desc: Option<String>,
     pub exec: Exec,
     pub path: Option<String>,
+    pub icon: Option<String>,
+    pub file: Option<PathBuf>,
     pub actions: Vec<Action>,
-    name_lower: String,
-    exec_lower: Option<String>,
+    pub generic_name: Option<String>,
+    pub keywords: Vec<String>,
+    pub categories: Vec<String>,
+    /// Whether the app runs in a terminal (`Terminal=true`)
+    pub terminal: bool,
+    /// Terminal emulator running the app, if it runs in a terminal
+    #[serde(skip)]
+    pub terminal_command: Option<TerminalCommand>,
+    /// Why the entry is normally hidden, if it's shown anyway (see `show_filtered` in the config)
+    #[serde(skip)]
+    pub filtered: Option<String>,
+    /// Program whose presence decides whether the entry is shown
+    try_exec: Option<String>,
+    /// Desktops the entry is exclusively shown in
+    only_show_in: Vec<String>,
+    /// Desktops the entry isn't shown in
+    not_show_in: Vec<String>,
+    /// Untranslated name (if the app name is localized), searchable alongside the localized one
+    untranslated_name: Option<String>,
+    /// Whether the app can be found by its command
+    search_exec: bool,
 }
 
 impl PartialEq for Application {
     fn eq(&self, other: &Self) -> bool {
-        self.name == other.name
+        self.id == other.id
     }
 }
 
 impl Hash for Application {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        self.name.hash(state);
+        self.id.hash(state);
     }
 }
 
-#[derive(Debug, Default, Clone, PartialEq, Eq)]
+/// Entry of the result list.
+#[derive(Debug, Clone)]
+pub enum Item {
+    App(Box<Application>),
+    /// Line read from stdin in dmenu mode
+    Line(String),
+    /// Result of the query evaluated as arithmetic, see [`crate::calc`]
+    Calc(String),
+    /// Command made of the query, see [`crate::fallback`] and [`crate::shortcut`]
+    Command {
+        app: Box<Application>,
+        note: Option<&'static str>,
+    },
+    /// Emoji or other character in chars mode
+    Char(Box<Character>),
+}
+
+impl Item {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::App(app) | Self::Command { app, .. } => &app.name,
+            Self::Char(c) => &c.name,
+            Self::Line(line) => line,
+            Self::Calc(result) => result,
+        }
+    }
+
+    pub fn icon(&self) -> Option<&str> {
+        match self {
+            Self::App(app) | Self::Command { app, .. } => app.icon.as_deref(),
+            Self::Line(_) | Self::Char(_) => None,
+            Self::Calc(_) => Some("accessories-calculator"),
+        }
+    }
+
+    /// Why the item is normally hidden (see [`Application::filtered`])
+    pub fn filtered(&self) -> Option<&str> {
+        match self {
+            Self::App(app) => app.filtered.as_deref(),
+            Self::Line(_) | Self::Calc(_) | Self::Command { .. } | Self::Char(_) => None,
+        }
+    }
+
+    /// Small print after the name
+    pub fn note(&self) -> Option<&str> {
+        match self {
+            Self::App(app) => app.filtered.as_deref(),
+            Self::Line(_) | Self::Char(_) => None,
+            Self::Calc(_) => Some("Enter to copy"),
+            Self::Command { note, .. } => *note,
+        }
+    }
+
+    pub fn actions(&self) -> &[Action] {
+        match self {
+            Self::App(app) => &app.actions,
+            Self::Line(_) | Self::Calc(_) | Self::Command { .. } | Self::Char(_) => &[],
+        }
+    }
+
+    /// Character shown in place of the icon
+    pub fn glyph(&self) -> Option<&str> {
+        match self {
+            Self::Char(c) => Some(&c.glyph),
+            _ => None,
+        }
+    }
+
+    pub fn score(&self, filter: &str) -> Option<Match> {
+        match self {
+            Self::App(app) => app.score(filter),
+            Self::Line(line) => fuzzy_match(line, filter),
+            Self::Char(c) => c.score(filter),
+            // derived from the query, not searched
+            Self::Calc(_) | Self::Command { .. } => None,
+        }
+    }
+}
+
+#[cfg(test)]
+impl Item {
+    /// Command line of a [`Item::Command`]
+    pub fn argv(&self) -> Vec<String> {
+        let Self::Command { app, .. } = self else {
+            panic!("not a command: {self:?}");
+        };
+
+        app.exec.expand(&FieldCodes {
+            name: &app.name,
+            icon: None,
+            file: None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Action {
     pub name: String,
-    pub exec: String,
+    pub exec: Exec,
 }
 
 impl Application {
@@ -43,34 +174,158 @@ impl Application {
         desc: Option<String>,
         exec: Exec,
         path: Option<String>,
+        icon: Option<String>,
+        file: Option<PathBuf>,
         actions: Vec<Action>,
     ) -> Self {
         const EXEC_EXCLUDE: &[&str] = &["steam"];
-        let name_lower = name.to_lowercase();
-        let use_exec_for_search = !EXEC_EXCLUDE
+        let search_exec = !EXEC_EXCLUDE
             .iter()
             .any(|exclude| exec.cmd.contains(exclude));
 
-        let exec_lower = use_exec_for_search.then_some(exec.cmd.to_lowercase());
+        // the file name is the ID of entries not nested in `applications/`
+        let id = file
+            .as_deref()
+            .and_then(Path::file_name)
+            .map(|file_name| file_name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| name.clone());
 
         Self {
+            id,
             name,
             desc,
             exec,
             path,
+            icon,
+            file,
             actions,
-            name_lower,
-            exec_lower,
+            generic_name: None,
+            keywords: vec![],
+            categories: vec![],
+            terminal: false,
+            terminal_command: None,
+            filtered: None,
+            try_exec: None,
+            only_show_in: vec![],
+            not_show_in: vec![],
+            untranslated_name: None,
+            search_exec,
+        }
+    }
+
+    /// A command line that isn't a desktop entry, e.g. running the query in a shell.
+    pub fn from_command(name: String, argv: Vec<String>, icon: &str) -> Self {
+        Self::new(
+            name,
+            None,
+            Exec::from_argv(argv),
+            None,
+            Some(icon.to_owned()),
+            None,
+            vec![],
+        )
+    }
+
+    /// An executable found in `PATH`, searched and launched by its file name.
+    pub fn from_executable(path: PathBuf) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_owned();
+        let exec = Exec::from_argv([path.to_str()?.to_owned()]);
+
+        Some(Self::new(
+            name,
+            None,
+            exec,
+            None,
+            Some("application-x-executable".to_owned()),
+            Some(path),
+            vec![],
+        ))
+    }
+
+    /// The app with `args` appended to its command line.
+    pub fn with_args(&self, args: impl IntoIterator<Item = String>) -> Self {
+        let mut app = self.clone();
+        app.exec.extend(args);
+
+        app
+    }
+
+    /// Sets the optional search metadata of a desktop entry.
+    fn with_metadata(
+        mut self,
+        untranslated_name: Option<String>,
+        generic_name: Option<String>,
+        keywords: Vec<String>,
+        categories: Vec<String>,
+    ) -> Self {
+        self.untranslated_name =
+            untranslated_name.filter(|untranslated| *untranslated != self.name);
+        self.generic_name = generic_name;
+        self.keywords = keywords;
+        self.categories = categories;
+
+        self
+    }
+
+    /// Sets the keys deciding whether a desktop entry is shown.
+    fn with_conditions(
+        mut self,
+        try_exec: Option<String>,
+        only_show_in: Vec<String>,
+        not_show_in: Vec<String>,
+    ) -> Self {
+        self.try_exec = try_exec;
+        self.only_show_in = only_show_in;
+        self.not_show_in = not_show_in;
+
+        self
+    }
+
+    /// Why the entry shouldn't be shown in `session`, according to `TryExec`, `OnlyShowIn`
+    /// and `NotShowIn`.
+    pub fn filter_reason(&self, session: &Session) -> Option<String> {
+        if let Some(try_exec) = &self.try_exec
+            && !session.has_program(try_exec)
+        {
+            return Some(format!("TryExec: {try_exec} not found"));
+        }
+
+        if !self.only_show_in.is_empty()
+            && !self
+                .only_show_in
+                .iter()
+                .any(|desktop| session.is_current_desktop(desktop))
+        {
+            return Some(format!("OnlyShowIn: {}", self.only_show_in.join(";")));
         }
+
+        self.not_show_in
+            .iter()
+            .find(|desktop| session.is_current_desktop(desktop))
+            .map(|desktop| format!("NotShowIn: {desktop}"))
     }
 
-    pub fn from_freedesktop_file(path: impl AsRef<Path>) -> Result<Option<Self>> {
-        let entry = freedesktop_entry_parser::parse_entry(path)?;
+    pub fn from_freedesktop_file(
+        path: impl AsRef<Path>,
+        locale: Option<&Locale>,
+    ) -> Result<Option<Self>> {
+        let file = path.as_ref().to_path_buf();
+        let entry = freedesktop_entry_parser::parse_entry(&file)?;
 
         let mut name = None;
+        let mut untranslated_name = None;
+        let mut generic_name = None;
+        let mut keywords = vec![];
+        let mut categories = vec![];
+        let mut terminal = false;
+        let mut try_exec = None;
+        let mut only_show_in = vec![];
+        let mut not_show_in = vec![];
+        let mut action_ids = vec![];
         let mut desc = None;
         let mut exec = None;
         let mut path = None;
+        let mut icon = None;
         let mut actions = vec![];
 
         for section in entry.sections() {
@@ -78,18 +333,17 @@ impl Application {
                 return Ok(None);
             }
 
-            let section_name = section
-                .attr("Name")
-                .map(|x| x.to_string())
-                .context("Name not found")?;
+            let section_name =
+                localized_attr(&section, "Name", locale).context("Name not found")?;
 
             let section_exec = section
                 .attr("Exec")
                 .map(|x| x.to_string())
                 .context("Exec not found")?;
 
-            let section_desc = section.attr("Comment").map(|x| x.to_string());
+            let section_desc = localized_attr(&section, "Comment", locale);
             let section_path = section.attr("Path").map(|x| x.to_string());
+            let section_icon = section.attr("Icon").map(|x| x.to_string());
 
             if section.name() == "Desktop Entry" {
                 anyhow::ensure!(
@@ -98,95 +352,488 @@ impl Application {
                 );
 
                 name = Some(section_name);
+                untranslated_name = section.attr("Name").map(|x| x.to_string());
                 exec = Some(
-                    section_exec
+                    unescape_string(&section_exec)
                         .parse()
                         .context("Failed to parse desktop file")?,
                 );
                 path = section_path;
                 desc = section_desc;
-            } else if section.name().contains("Desktop Action") {
+                icon = section_icon;
+                generic_name = localized_attr(&section, "GenericName", locale);
+                action_ids = section.attr("Actions").map(split_list).unwrap_or_default();
+                terminal = section.attr("Terminal") == Some("true");
+                try_exec = section.attr("TryExec").map(unescape_string);
+                only_show_in = section
+                    .attr("OnlyShowIn")
+                    .map(split_list)
+                    .unwrap_or_default();
+                not_show_in = section
+                    .attr("NotShowIn")
+                    .map(split_list)
+                    .unwrap_or_default();
+                categories = section
+                    .attr("Categories")
+                    .map(split_list)
+                    .unwrap_or_default();
+
+                // keep the untranslated keywords searchable, like the name
+                keywords = localized_attr(&section, "Keywords", locale)
+                    .as_deref()
+                    .map(split_list)
+                    .unwrap_or_default();
+                for keyword in section.attr("Keywords").map(split_list).unwrap_or_default() {
+                    if !keywords.contains(&keyword) {
+                        keywords.push(keyword);
+                    }
+                }
+            } else if let Some(action_id) = section.name().strip_prefix("Desktop Action ") {
                 let action = Action {
                     name: section_name,
-                    exec: section_exec,
+                    exec: unescape_string(&section_exec)
+                        .parse()
+                        .context("Failed to parse desktop action")?,
                 };
-                actions.push(action);
+                actions.push((action_id.to_owned(), action));
             }
         }
 
+        // sections come in no particular order, so follow the `Actions` key; action groups
+        // missing from it must be ignored
+        let actions = action_ids
+            .iter()
+            .filter_map(|action_id| {
+                let idx = actions.iter().position(|(id, _)| id == action_id)?;
+                Some(actions.swap_remove(idx).1)
+            })
+            .collect();
+
         anyhow::ensure!(
             name.is_some() && exec.is_some(),
             "Section 'Desktop Entry' not found"
         );
 
-        Ok(Some(Self::new(
+        let mut app = Self::new(
             name.unwrap(),
             desc,
             exec.unwrap(),
             path,
+            icon,
+            Some(file),
             actions,
-        )))
+        )
+        .with_metadata(untranslated_name, generic_name, keywords, categories)
+        .with_conditions(try_exec, only_show_in, not_show_in);
+        app.terminal = terminal;
+
+        Ok(Some(app))
     }
 
     pub fn exec(&self) -> Result<()> {
-        log::info!("Executing {}", self.exec);
+        self.replace_process(&self.exec)
+    }
 
-        let Exec { cmd } = &self.exec;
+    pub fn exec_action(&self, action: &Action) -> Result<()> {
+        self.replace_process(&action.exec)
+    }
 
-        let mut command = std::process::Command::new("sh");
-        command.arg("-c");
-        command.arg(cmd);
+    /// Starts the app (or one of its actions) in a new session, detached from the launcher.
+    ///
+    /// Output goes to `log` if given, or is discarded. Failing to start the program (e.g. a
+    /// missing binary or working directory) is reported, the app exiting later is not.
+    pub fn spawn(&self, action: Option<&Action>, log: Option<&Path>) -> Result<()> {
+        let mut command = self.background_command(action, log)?;
 
-        if let Some(path) = self.path.as_ref() {
-            command.current_dir(path);
+        // SAFETY: `detach` only calls async-signal-safe functions
+        unsafe { command.pre_exec(detach) };
+
+        // exec errors of the app are reported through the intermediate process
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to execute {}", command.get_program().display()))?;
+        child.wait()?;
+
+        Ok(())
+    }
+
+    /// Like `spawn`, but places the app in a transient systemd scope of its own.
+    ///
+    /// Falls back to `spawn` if the systemd user manager can't be reached.
+    pub fn spawn_in_scope(&self, action: Option<&Action>, log: Option<&Path>) -> Result<()> {
+        match systemd::Manager::connect() {
+            Ok(manager) => self.spawn_with_manager(action, log, &manager),
+            Err(err) => {
+                log::info!("starting {} without a scope: {err:#}", self.name);
+                self.spawn(action, log)
+            }
+        }
+    }
+
+    fn spawn_with_manager(
+        &self,
+        action: Option<&Action>,
+        log: Option<&Path>,
+        manager: &systemd::Manager,
+    ) -> Result<()> {
+        let mut command = self.background_command(action, log)?;
+
+        // the scope needs the PID of the app, so there's no second fork
+        // SAFETY: `new_session` only calls async-signal-safe functions
+        unsafe { command.pre_exec(new_session) };
+
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("Failed to execute {}", command.get_program().display()))?;
+
+        // the app runs either way, just without the scope
+        match manager.start_scope(&self.id, child.id()) {
+            Ok(scope) => log::info!("started {} in {scope}", self.name),
+            Err(err) => log::warn!("failed to place {} in a scope: {err:#}", self.name),
         }
 
-        let _ = command.exec();
+        // reap the app once it exits, if the launcher is still running then
+        thread::spawn(move || child.wait());
 
         Ok(())
     }
 
-    pub fn score(&self, filter: &str) -> f64 {
-        let filter = filter.to_lowercase();
+    /// Command running `action` (or the app) without input, its output going to `log`
+    fn background_command(&self, action: Option<&Action>, log: Option<&Path>) -> Result<Command> {
+        let exec = action.map_or(&self.exec, |action| &action.exec);
+        let mut command = self.command(exec)?;
+
+        let (stdout, stderr) = match log {
+            Some(log) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(log)
+                    .with_context(|| format!("Failed to open {}", log.display()))?;
+                (Stdio::from(file.try_clone()?), Stdio::from(file))
+            }
+            None => (Stdio::null(), Stdio::null()),
+        };
+        command.stdin(Stdio::null()).stdout(stdout).stderr(stderr);
+
+        Ok(command)
+    }
+
+    fn replace_process(&self, exec: &Exec) -> Result<()> {
+        let mut command = self.command(exec)?;
+
+        // `exec` only returns on failure
+        let err = command.exec();
+
+        Err(err).with_context(|| format!("Failed to execute {}", command.get_program().display()))
+    }
+
+    fn command(&self, exec: &Exec) -> Result<Command> {
+        log::info!("Executing {exec}");
 
-        let score_str = |string: &str| {
-            if string.contains(&filter) {
-                return 1.0;
+        let mut argv = exec.expand(&FieldCodes {
+            name: &self.name,
+            icon: self.icon.as_deref(),
+            file: self.file.as_deref(),
+        });
+
+        if self.terminal {
+            match &self.terminal_command {
+                Some(terminal) => argv = terminal.wrap(argv),
+                None => log::warn!(
+                    "no terminal emulator found for {}; set `terminal` in the config",
+                    self.name
+                ),
             }
+        }
+
+        let (program, args) = argv
+            .split_first()
+            .context("Exec expands to an empty command line")?;
 
-            strsim::normalized_levenshtein(string, &filter)
+        let mut command = Command::new(program);
+        command.args(args);
+
+        if let Some(path) = self.path.as_ref() {
+            command.current_dir(path);
+        }
+
+        Ok(command)
+    }
+
+    /// Fuzzy matches the app against `filter`.
+    ///
+    /// The name is matched first, other fields (with lower weights) only raise the score;
+    /// matched indices are reported for the name only.
+    pub fn score(&self, filter: &str) -> Option<Match> {
+        const GENERIC_NAME_WEIGHT: f64 = 0.8;
+        const KEYWORDS_WEIGHT: f64 = 0.7;
+        const CATEGORIES_WEIGHT: f64 = 0.5;
+        const EXEC_WEIGHT: f64 = 0.5;
+
+        let score_str = |string: &str| fuzzy_match(string, filter).map_or(0.0, |m| m.score);
+        let score_list =
+            |strings: &[String]| strings.iter().map(|x| score_str(x)).fold(0.0, f64::max);
+
+        let exec = if self.search_exec {
+            score_str(&self.exec.cmd)
+        } else {
+            0.0
         };
 
-        score_str(&self.name_lower)
-            .max(self.exec_lower.as_deref().map(score_str).unwrap_or(0.0) * 0.5)
+        let other = self
+            .untranslated_name
+            .as_deref()
+            .map(score_str)
+            .unwrap_or(0.0)
+            .max(self.generic_name.as_deref().map(score_str).unwrap_or(0.0) * GENERIC_NAME_WEIGHT)
+            .max(score_list(&self.keywords) * KEYWORDS_WEIGHT)
+            .max(score_list(&self.categories) * CATEGORIES_WEIGHT)
+            .max(exec * EXEC_WEIGHT);
+
+        match fuzzy_match(&self.name, filter) {
+            Some(name) if name.score >= other => Some(name),
+            _ => (other > 0.0).then(|| Match {
+                score: other,
+                indices: vec![],
+            }),
+        }
     }
 }
 
-fn trim_arg(s: &str) -> &str {
-    let mut out = s;
-    [
-        "%f", "%F", "%u", "%U", "%d", "%D", "%n", "%N", "%i", "%c", "%v", "%m",
-    ]
-    .iter()
-    .for_each(|pat| out = out.trim_end_matches(pat));
+/// Looks up a localized value of `key`, falling back to the untranslated one.
+fn localized_attr(
+    section: &AttrSelector<&str>,
+    key: &str,
+    locale: Option<&Locale>,
+) -> Option<String> {
+    locale
+        .into_iter()
+        .flat_map(Locale::candidates)
+        .find_map(|locale| section.attr_with_param(key, locale).map(|x| x.to_string()))
+        .or_else(|| section.attr(key).map(|x| x.to_string()))
+}
 
-    out
+/// Splits a `;`-separated list value, honoring `\;` escapes.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = vec![];
+    let mut item = String::new();
+    let mut chars = value.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.as_str().starts_with(';') => {
+                item.push(';');
+                chars.next();
+            }
+            ';' => items.push(std::mem::take(&mut item)),
+            c => item.push(c),
+        }
+    }
+    items.push(item);
+
+    items.retain(|item| !item.trim().is_empty());
+    items
 }
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+/// Values substituted for the `%c`, `%i` and `%k` field codes.
+pub struct FieldCodes<'a> {
+    pub name: &'a str,
+    pub icon: Option<&'a str>,
+    pub file: Option<&'a Path>,
+}
+
+/// Parsed `Exec` key: a list of arguments, each made of literal text and field codes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Exec {
     pub cmd: String,
+    args: Vec<Vec<Token>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum Token {
+    Literal(String),
+    Field(char),
+}
+
+impl Exec {
+    /// Command line of literal arguments, without field codes.
+    pub fn from_argv(argv: impl IntoIterator<Item = String>) -> Self {
+        let mut exec = Self {
+            cmd: String::new(),
+            args: vec![],
+        };
+        exec.extend(argv);
+
+        exec
+    }
+
+    /// Appends literal arguments.
+    fn extend(&mut self, args: impl IntoIterator<Item = String>) {
+        for arg in args {
+            if !self.cmd.is_empty() {
+                self.cmd.push(' ');
+            }
+            self.cmd.push_str(&shell_words::quote(&arg));
+            self.args.push(vec![Token::Literal(arg)]);
+        }
+    }
+
+    /// Builds the argv, expanding or removing field codes.
+    ///
+    /// runi never passes files or URLs, so `%f`, `%F`, `%u` and `%U` expand to nothing.
+    pub fn expand(&self, codes: &FieldCodes) -> Vec<String> {
+        let mut argv = vec![];
+
+        for arg in &self.args {
+            if let [Token::Field('i')] = arg.as_slice() {
+                if let Some(icon) = codes.icon {
+                    argv.push("--icon".to_owned());
+                    argv.push(icon.to_owned());
+                }
+                continue;
+            }
+
+            // file and URL lists, plus deprecated codes, are removed along with their argument
+            let removed = !arg.is_empty()
+                && arg.iter().all(
+                    |token| matches!(token, Token::Field(code) if !matches!(code, 'c' | 'i' | 'k')),
+                );
+
+            if !removed {
+                argv.push(expand_field(arg, codes));
+            }
+        }
+
+        argv
+    }
+}
+
+fn expand_field(arg: &[Token], codes: &FieldCodes) -> String {
+    let mut out = String::new();
+
+    for token in arg {
+        match token {
+            Token::Literal(literal) => out.push_str(literal),
+            Token::Field('c') => out.push_str(codes.name),
+            Token::Field('i') => out.push_str(codes.icon.unwrap_or_default()),
+            Token::Field('k') => {
+                if let Some(file) = codes.file {
+                    out.push_str(&file.to_string_lossy());
+                }
+            }
+            Token::Field(_) => {}
+        }
+    }
+
+    out
+}
+
+/// Splits an `Exec` value into arguments following the Desktop Entry quoting rules.
+fn tokenize(s: &str) -> Result<Vec<Vec<Token>>> {
+    const FIELD_CODES: &str = "fFuUdDnNickvm";
+
+    fn push_char(arg: &mut Vec<Token>, c: char) {
+        match arg.last_mut() {
+            Some(Token::Literal(literal)) => literal.push(c),
+            _ => arg.push(Token::Literal(c.to_string())),
+        }
+    }
+
+    let mut args = vec![];
+    let mut arg = vec![];
+    // `""` is an empty argument, so track whether the current one was started
+    let mut in_arg = false;
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ' ' | '\t' | '\n' => {
+                if in_arg {
+                    args.push(std::mem::take(&mut arg));
+                    in_arg = false;
+                }
+                continue;
+            }
+            '"' => loop {
+                match chars.next().context("Unterminated double quote")? {
+                    '"' => break,
+                    '\\' => match chars.next().context("Unterminated double quote")? {
+                        c @ ('"' | '`' | '$' | '\\') => push_char(&mut arg, c),
+                        c => {
+                            push_char(&mut arg, '\\');
+                            push_char(&mut arg, c);
+                        }
+                    },
+                    c => push_char(&mut arg, c),
+                }
+            },
+            '\'' => loop {
+                match chars.next().context("Unterminated single quote")? {
+                    '\'' => break,
+                    c => push_char(&mut arg, c),
+                }
+            },
+            '\\' => push_char(&mut arg, chars.next().unwrap_or('\\')),
+            '%' => match chars.next() {
+                Some('%') => push_char(&mut arg, '%'),
+                Some(code) if FIELD_CODES.contains(code) => arg.push(Token::Field(code)),
+                Some(code) => anyhow::bail!("Invalid field code %{code}"),
+                None => anyhow::bail!("Incomplete field code at the end of Exec"),
+            },
+            c => push_char(&mut arg, c),
+        }
+
+        in_arg = true;
+    }
+
+    if in_arg {
+        args.push(arg);
+    }
+
+    Ok(args)
+}
+
+/// Resolves the escape sequences of the `string` value type (`\s`, `\n`, `\t`, `\r`, `\\`).
+fn unescape_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('s') => out.push(' '),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('\\') => out.push('\\'),
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => out.push('\\'),
+        }
+    }
+
+    out
 }
 
 impl FromStr for Exec {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let sanitized = s.trim();
-        let sanitized = trim_arg(sanitized);
+        let cmd = s.trim();
+        let args = tokenize(cmd)?;
+
+        anyhow::ensure!(!args.is_empty(), "Exec is empty");
 
         Ok(Self {
-            cmd: sanitized.to_string(),
+            cmd: cmd.to_string(),
+            args,
         })
     }
 }
@@ -197,50 +844,434 @@ impl Display for Exec {
     }
 }
 
+/// Terminal emulator command template, e.g. `alacritty -e {cmd}`.
+///
+/// An argument equal to `{cmd}` is replaced with the arguments of the app, while `{cmd}`
+/// inside a longer argument is replaced with the whole quoted command line; if missing,
+/// the command is appended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerminalCommand {
+    args: Vec<String>,
+}
+
+impl TerminalCommand {
+    const PLACEHOLDER: &str = "{cmd}";
+
+    /// Command running an app in terminal emulator `args` (its program with optional
+    /// arguments), using the usual option of the emulator.
+    pub fn for_terminal(mut args: Vec<String>) -> Option<Self> {
+        let program = args.first()?;
+        let name = Path::new(program).file_name()?.to_str()?;
+
+        let separator: &[&str] = match name {
+            "gnome-terminal" | "kgx" | "ptyxis" | "tilix" => &["--"],
+            "wezterm" => &["start", "--"],
+            "kitty" | "foot" => &[],
+            _ => &["-e"],
+        };
+
+        args.extend(separator.iter().map(|arg| arg.to_string()));
+        args.push(Self::PLACEHOLDER.to_owned());
+
+        Some(Self { args })
+    }
+
+    pub fn wrap(&self, argv: Vec<String>) -> Vec<String> {
+        let mut wrapped = Vec::with_capacity(self.args.len() + argv.len());
+
+        for arg in &self.args {
+            if arg == Self::PLACEHOLDER {
+                wrapped.extend(argv.iter().cloned());
+            } else if arg.contains(Self::PLACEHOLDER) {
+                wrapped.push(arg.replace(Self::PLACEHOLDER, &shell_words::join(&argv)));
+            } else {
+                wrapped.push(arg.clone());
+            }
+        }
+
+        wrapped
+    }
+}
+
+impl FromStr for TerminalCommand {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut args = shell_words::split(s)?;
+
+        anyhow::ensure!(!args.is_empty(), "terminal command is empty");
+        if !args.iter().any(|arg| arg.contains(Self::PLACEHOLDER)) {
+            args.push(Self::PLACEHOLDER.to_owned());
+        }
+
+        Ok(Self { args })
+    }
+}
+
+/// Runs in the forked child before exec: a new session, then a second fork.
+///
+/// The app is then the child of an exited process, so it's adopted by init (or the closest
+/// subreaper) instead of the launcher, and never gets a controlling terminal.
+fn detach() -> std::io::Result<()> {
+    new_session()?;
+
+    // SAFETY: fork and _exit are async-signal-safe
+    unsafe {
+        match libc::fork() {
+            -1 => Err(std::io::Error::last_os_error()),
+            0 => Ok(()),
+            _ => libc::_exit(0),
+        }
+    }
+}
+
+/// Runs in the forked child before exec, detaching it from the session of the launcher.
+fn new_session() -> std::io::Result<()> {
+    // SAFETY: setsid is async-signal-safe
+    if unsafe { libc::setsid() } < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
 
     #[test]
     fn test_simple_freedesktop_file() {
-        let application = Application::from_freedesktop_file(format!(
-            "{}/test/Alacritty.desktop",
-            env!("CARGO_MANIFEST_DIR")
-        ))
+        let application = Application::from_freedesktop_file(
+            format!("{}/test/Alacritty.desktop", env!("CARGO_MANIFEST_DIR")),
+            None,
+        )
         .unwrap()
         .unwrap();
 
         assert_eq!(application.name, "Alacritty");
-        assert_eq!(
-            application.exec,
-            Exec {
-                cmd: "alacritty".to_string(),
-            }
-        );
+        assert_eq!(application.exec.cmd, "alacritty");
+        assert_eq!(application.icon.as_deref(), Some("Alacritty"));
         assert_eq!(
             application.desc,
             Some("A fast, cross-platform, OpenGL terminal emulator".to_string())
         );
         assert_eq!(application.actions.len(), 1);
         assert_eq!(application.actions[0].name, "New Terminal");
-        assert_eq!(application.actions[0].exec, "alacritty");
+        assert_eq!(application.actions[0].exec.cmd, "alacritty");
+        assert_eq!(application.generic_name.as_deref(), Some("Terminal"));
+        assert_eq!(application.categories, ["System", "TerminalEmulator"]);
+    }
+
+    #[test]
+    fn test_search_metadata() {
+        let alacritty = Application::from_freedesktop_file(
+            format!("{}/test/Alacritty.desktop", env!("CARGO_MANIFEST_DIR")),
+            None,
+        )
+        .unwrap()
+        .unwrap();
+        let firefox = Application::from_freedesktop_file(
+            format!("{}/test/firefox.desktop", env!("CARGO_MANIFEST_DIR")),
+            Some(&"pl_PL".parse().unwrap()),
+        )
+        .unwrap()
+        .unwrap();
+
+        // localized keywords come first, the untranslated ones are appended
+        assert_eq!(firefox.keywords[..3], ["Internet", "WWW", "Przeglądarka"]);
+        assert!(firefox.keywords.iter().any(|x| x == "Browser"));
+
+        let score = |app: &Application, filter| app.score(filter).map_or(0.0, |m| m.score);
+
+        assert_eq!(score(&alacritty, "terminal"), 0.8);
+        assert_eq!(score(&firefox, "explorer"), 0.7);
+        assert_eq!(score(&firefox, "network"), 0.5);
+        assert!(score(&firefox, "terminal") < score(&alacritty, "terminal"));
+        assert!(score(&alacritty, "browser") < score(&firefox, "browser"));
+
+        // matched characters are reported for the name only
+        assert_eq!(alacritty.score("acr").unwrap().indices, [0, 3, 4]);
+        assert!(alacritty.score("terminal").unwrap().indices.is_empty());
+    }
+
+    #[test]
+    fn test_show_conditions() {
+        let alacritty = Application::from_freedesktop_file(
+            format!("{}/test/Alacritty.desktop", env!("CARGO_MANIFEST_DIR")),
+            None,
+        )
+        .unwrap()
+        .unwrap();
+        let mut settings = Application::from_freedesktop_file(
+            format!("{}/test/gnome-settings.desktop", env!("CARGO_MANIFEST_DIR")),
+            None,
+        )
+        .unwrap()
+        .unwrap();
+        // TryExec is checked against PATH, `sh` surely is there
+        settings.try_exec = Some("sh".to_owned());
+
+        let path = || vec!["/usr/bin".into(), "/bin".into()];
+        let gnome = Session::new(vec!["ubuntu".to_owned(), "GNOME".to_owned()], path());
+        let unity = Session::new(vec!["Unity".to_owned()], path());
+        let sway = Session::new(vec!["sway".to_owned()], path());
+
+        assert_eq!(
+            alacritty
+                .filter_reason(&Session::new(vec![], vec![]))
+                .as_deref(),
+            Some("TryExec: alacritty not found")
+        );
+        assert_eq!(settings.filter_reason(&gnome), None);
+        assert_eq!(
+            settings.filter_reason(&unity).as_deref(),
+            Some("NotShowIn: Unity")
+        );
+        assert_eq!(
+            settings.filter_reason(&sway).as_deref(),
+            Some("OnlyShowIn: GNOME;Unity")
+        );
+    }
+
+    #[test]
+    fn test_split_list() {
+        assert_eq!(split_list("a;b\\;c;;d;"), ["a", "b;c", "d"]);
+        assert!(split_list("").is_empty());
     }
 
     #[test]
     fn test_wineapp() {
-        let application = Application::from_freedesktop_file(format!(
-            "{}/test/Guitar Pro 7.desktop",
-            env!("CARGO_MANIFEST_DIR")
-        ))
+        let application = Application::from_freedesktop_file(
+            format!("{}/test/Guitar Pro 7.desktop", env!("CARGO_MANIFEST_DIR")),
+            None,
+        )
         .unwrap()
         .unwrap();
 
         assert_eq!(application.name, "Guitar Pro 7");
         assert_eq!(
-            application.exec,
-            Exec {
-                cmd: r#"env WINEPREFIX="/home/marcin/.wine" wine C:\\\\ProgramData\\\\Microsoft\\\\Windows\\\\Start\\ Menu\\\\Programs\\\\Guitar\\ Pro\\ 7\\\\Guitar\\ Pro\\ 7.lnk"#.to_owned(),
+            application.exec.cmd,
+            r#"env WINEPREFIX="/home/marcin/.wine" wine C:\\ProgramData\\Microsoft\\Windows\\Start\ Menu\\Programs\\Guitar\ Pro\ 7\\Guitar\ Pro\ 7.lnk"#
+        );
+        assert_eq!(
+            application.exec.expand(&CODES),
+            [
+                "env",
+                "WINEPREFIX=/home/marcin/.wine",
+                "wine",
+                r"C:\ProgramData\Microsoft\Windows\Start Menu\Programs\Guitar Pro 7\Guitar Pro 7.lnk",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_localized_freedesktop_file() {
+        let path = format!("{}/test/firefox.desktop", env!("CARGO_MANIFEST_DIR"));
+
+        let locale: Locale = "pl_PL.UTF-8".parse().unwrap();
+        let application = Application::from_freedesktop_file(&path, Some(&locale))
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(application.name, "Przeglądarka Firefox");
+        assert_eq!(application.desc.as_deref(), Some("Przeglądanie stron WWW"));
+        assert_eq!(application.actions[0].name, "Nowe okno");
+        assert_eq!(application.actions[1].name, "Nowe okno prywatne");
+        // the profile manager action isn't listed in `Actions`
+        assert_eq!(application.actions.len(), 2);
+        // the untranslated name stays searchable
+        assert!(application.score("web browser").unwrap().score > 0.8);
+
+        let locale: Locale = "de_AT".parse().unwrap();
+        let application = Application::from_freedesktop_file(&path, Some(&locale))
+            .unwrap()
+            .unwrap();
+        assert_eq!(application.name, "Firefox-Webbrowser");
+
+        let locale: Locale = "fr_FR".parse().unwrap();
+        let application = Application::from_freedesktop_file(&path, Some(&locale))
+            .unwrap()
+            .unwrap();
+        assert_eq!(application.name, "Firefox Web Browser");
+    }
+
+    const CODES: FieldCodes = FieldCodes {
+        name: "Foo Bar",
+        icon: Some("foo"),
+        file: None,
+    };
+
+    fn argv(exec: &str) -> Vec<String> {
+        exec.parse::<Exec>().unwrap().expand(&FieldCodes {
+            file: Some(Path::new("/usr/share/applications/foo.desktop")),
+            ..CODES
+        })
+    }
+
+    #[test]
+    fn test_exec_field_codes() {
+        assert_eq!(argv("foo %U --flag"), ["foo", "--flag"]);
+        assert_eq!(argv("foo %f"), ["foo"]);
+        assert_eq!(argv("foo %i"), ["foo", "--icon", "foo"]);
+        assert_eq!(argv("foo --name %c"), ["foo", "--name", "Foo Bar"]);
+        assert_eq!(
+            argv("foo --desktop=%k"),
+            ["foo", "--desktop=/usr/share/applications/foo.desktop"]
+        );
+        assert_eq!(argv("foo 100%% %d %D%n %N %v %m"), ["foo", "100%"]);
+
+        let no_icon = FieldCodes {
+            icon: None,
+            ..CODES
+        };
+        assert_eq!(
+            "foo %i %u".parse::<Exec>().unwrap().expand(&no_icon),
+            ["foo"]
+        );
+
+        assert!("foo %x".parse::<Exec>().is_err());
+    }
+
+    #[test]
+    fn test_exec_quoting() {
+        assert_eq!(
+            argv(r#""/opt/My App/app" --title "say \"hi\"" '' end"#),
+            ["/opt/My App/app", "--title", r#"say "hi""#, "", "end"]
+        );
+        assert_eq!(argv(r#"sh -c "echo \$HOME""#), ["sh", "-c", "echo $HOME"]);
+        assert!(r#"foo "bar"#.parse::<Exec>().is_err());
+        assert!("".parse::<Exec>().is_err());
+    }
+
+    #[test]
+    fn test_executable() {
+        let app = Application::from_executable("/usr/bin/grep".into()).unwrap();
+        assert_eq!(app.name, "grep");
+        assert_eq!(app.id, "grep");
+        assert!(app.score("grp").is_some());
+
+        let with_args = app.with_args(["-r".to_owned(), "two words".to_owned()]);
+        assert_eq!(with_args.exec.cmd, "/usr/bin/grep -r 'two words'");
+        assert_eq!(
+            with_args.exec.expand(&CODES),
+            ["/usr/bin/grep", "-r", "two words"]
+        );
+        // the indexed entry is left alone
+        assert_eq!(app.exec.cmd, "/usr/bin/grep");
+    }
+
+    #[test]
+    fn test_spawn() {
+        let dir = std::env::temp_dir().join(format!("runi-spawn-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("apps.log");
+
+        let app = |exec: &str, path: Option<&Path>| {
+            Application::new(
+                "Test".to_owned(),
+                None,
+                exec.parse().unwrap(),
+                path.map(|path| path.display().to_string()),
+                None,
+                None,
+                vec![],
+            )
+        };
+
+        // the app outlives the spawn, its output shows up eventually
+        app(r#"sh -c "sleep 0.1; echo \$PPID""#, Some(&dir))
+            .spawn(None, Some(&log))
+            .unwrap();
+        let started = std::time::Instant::now();
+        let parent = loop {
+            let output = std::fs::read_to_string(&log).unwrap_or_default();
+            if !output.is_empty() {
+                break output;
             }
+            assert!(started.elapsed().as_secs() < 10, "the app didn't run");
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        };
+        // adopted by some reaper once the intermediate process exited
+        assert_ne!(parent.trim(), std::process::id().to_string());
+
+        let missing = app("runi-missing-binary", None).spawn(None, None);
+        assert!(format!("{:#}", missing.unwrap_err()).contains("runi-missing-binary"));
+
+        let bad_cwd = app("true", Some(&dir.join("missing"))).spawn(None, None);
+        assert!(bad_cwd.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_spawn_in_scope() {
+        let started = systemd::test::Started::default();
+        let (_server, client) = systemd::test::mock_bus(started.clone());
+        let manager = systemd::Manager::with_connection(&client).unwrap();
+
+        let app = Application::new(
+            "Test".to_owned(),
+            None,
+            "true".parse().unwrap(),
+            None,
+            None,
+            Some(PathBuf::from("/usr/share/applications/test-app.desktop")),
+            vec![],
         );
+        app.spawn_with_manager(None, None, &manager).unwrap();
+
+        let started = started.lock().unwrap();
+        assert_eq!(started.len(), 1);
+        assert!(started[0].0.starts_with("app-runi-test\\x2dapp-"));
+
+        let missing = Application {
+            exec: "runi-missing-binary".parse().unwrap(),
+            ..app.clone()
+        };
+        assert!(missing.spawn_with_manager(None, None, &manager).is_err());
+    }
+
+    #[test]
+    fn test_terminal_command() {
+        let htop = Application::from_freedesktop_file(
+            format!("{}/test/htop.desktop", env!("CARGO_MANIFEST_DIR")),
+            None,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(htop.terminal);
+
+        let argv = || vec!["htop".to_owned(), "--tree".to_owned()];
+        let wrap = |terminal: &str| terminal.parse::<TerminalCommand>().unwrap().wrap(argv());
+
+        assert_eq!(
+            wrap("alacritty -e {cmd}"),
+            ["alacritty", "-e", "htop", "--tree"]
+        );
+        assert_eq!(wrap("kitty"), ["kitty", "htop", "--tree"]);
+        assert_eq!(
+            wrap("foot sh -c '{cmd}; read'"),
+            ["foot", "sh", "-c", "htop --tree; read"]
+        );
+        assert!("".parse::<TerminalCommand>().is_err());
+
+        let detected = |args: &[&str]| {
+            TerminalCommand::for_terminal(args.iter().map(|arg| arg.to_string()).collect())
+                .unwrap()
+                .wrap(argv())
+        };
+        assert_eq!(
+            detected(&["/usr/bin/xterm"]),
+            ["/usr/bin/xterm", "-e", "htop", "--tree"]
+        );
+        assert_eq!(
+            detected(&["gnome-terminal", "--wait"]),
+            ["gnome-terminal", "--wait", "--", "htop", "--tree"]
+        );
+    }
+
+    #[test]
+    fn test_unescape_string() {
+        assert_eq!(unescape_string(r"a\sb\\c\td"), "a b\\c\td");
     }
 }
@@ -0,0 +1,67 @@
+use std::{
+    env,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+/// Properties of the running session deciding which desktop entries are shown.
+#[derive(Debug, Clone, Default)]
+pub struct Session {
+    /// Desktop names from `XDG_CURRENT_DESKTOP`, e.g. `GNOME` or `sway`
+    desktops: Vec<String>,
+
+    /// Directories from `PATH`
+    path: Vec<PathBuf>,
+}
+
+impl Session {
+    pub fn new(desktops: Vec<String>, path: Vec<PathBuf>) -> Self {
+        Self { desktops, path }
+    }
+
+    /// Reads `XDG_CURRENT_DESKTOP` and `PATH`.
+    pub fn from_env() -> Self {
+        let desktops = env::var("XDG_CURRENT_DESKTOP")
+            .map(|desktops| {
+                desktops
+                    .split(':')
+                    .filter(|desktop| !desktop.is_empty())
+                    .map(str::to_owned)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let path = env::var_os("PATH")
+            .map(|path| env::split_paths(&path).collect())
+            .unwrap_or_default();
+
+        Self::new(desktops, path)
+    }
+
+    /// Directories searched for programs, in order of precedence
+    pub fn path(&self) -> &[PathBuf] {
+        &self.path
+    }
+
+    pub fn is_current_desktop(&self, desktop: &str) -> bool {
+        self.desktops.iter().any(|current| current == desktop)
+    }
+
+    /// Whether `program` is an executable file, given by an absolute path or found in `PATH`.
+    pub fn has_program(&self, program: &str) -> bool {
+        let program = Path::new(program);
+
+        if program.is_absolute() {
+            return is_executable(program);
+        }
+
+        self.path
+            .iter()
+            .any(|dir| is_executable(&dir.join(program)))
+    }
+}
+
+pub(crate) fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+}
@@ -0,0 +1,148 @@
+use crate::{
+    config::{Shortcut, ShortcutTarget},
+    fallback,
+    model::{Application, Item, TerminalCommand},
+};
+
+/// Icons of the shortcut rows
+pub const ICONS: &[&str] = &["web-browser", "system-run", "utilities-terminal"];
+
+/// The row of the shortcut whose keyword leads `query`, if there's text after it.
+///
+/// Shortcuts running in a terminal are left out if no terminal emulator is known.
+pub fn item(
+    shortcuts: &[Shortcut],
+    query: &str,
+    terminal: Option<&TerminalCommand>,
+) -> Option<Item> {
+    let query = query.trim();
+    let (keyword, arg) = query.split_once(char::is_whitespace)?;
+    let arg = arg.trim_start();
+
+    let shortcut = shortcuts
+        .iter()
+        .find(|shortcut| shortcut.keyword == keyword)?;
+    let name = format!(
+        "{}: {arg}",
+        shortcut.name.as_deref().unwrap_or(&shortcut.keyword)
+    );
+
+    let app = match &shortcut.target {
+        ShortcutTarget::Url { url } => Application::from_command(
+            name,
+            vec!["xdg-open".to_owned(), fallback::expand_url(url, arg)],
+            "web-browser",
+        ),
+        ShortcutTarget::Command {
+            command,
+            terminal: false,
+        } => Application::from_command(name, shell(command, arg), "system-run"),
+        ShortcutTarget::Command {
+            command,
+            terminal: true,
+        } => {
+            let mut app =
+                Application::from_command(name, shell(command, arg), "utilities-terminal");
+            app.terminal = true;
+            app.terminal_command = Some(terminal?.clone());
+            app
+        }
+    };
+
+    Some(Item::Command {
+        app: Box::new(app),
+        note: Some("shortcut"),
+    })
+}
+
+/// `sh -c` running `command` with `{query}` replaced by the shell-quoted `arg`
+fn shell(command: &str, arg: &str) -> Vec<String> {
+    let script = command.replace("{query}", &shell_words::quote(arg));
+
+    vec!["sh".to_owned(), "-c".to_owned(), script]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn shortcuts() -> Vec<Shortcut> {
+        vec![
+            Shortcut {
+                keyword: "gh".to_owned(),
+                name: Some("GitHub".to_owned()),
+                target: ShortcutTarget::Url {
+                    url: "https://github.com/search?q={query}".to_owned(),
+                },
+            },
+            Shortcut {
+                keyword: "man".to_owned(),
+                name: None,
+                target: ShortcutTarget::Command {
+                    command: "man {query}".to_owned(),
+                    terminal: true,
+                },
+            },
+            Shortcut {
+                keyword: "say".to_owned(),
+                name: None,
+                target: ShortcutTarget::Command {
+                    command: "notify-send {query}".to_owned(),
+                    terminal: false,
+                },
+            },
+        ]
+    }
+
+    fn argv(item: Option<Item>) -> (String, Vec<String>) {
+        let item = item.expect("no shortcut matches");
+        (item.name().to_owned(), item.argv())
+    }
+
+    #[test]
+    fn test_url() {
+        assert_eq!(
+            argv(item(&shortcuts(), "gh  rust & egui", None)),
+            (
+                "GitHub: rust & egui".to_owned(),
+                vec![
+                    "xdg-open".to_owned(),
+                    "https://github.com/search?q=rust%20%26%20egui".to_owned()
+                ]
+            )
+        );
+    }
+
+    #[test]
+    fn test_command() {
+        let terminal: TerminalCommand = "foot {cmd}".parse().unwrap();
+
+        assert_eq!(
+            argv(item(&shortcuts(), "say it's $HOME", None)),
+            (
+                "say: it's $HOME".to_owned(),
+                vec![
+                    "sh".to_owned(),
+                    "-c".to_owned(),
+                    r#"notify-send 'it'\''s $HOME'"#.to_owned()
+                ]
+            )
+        );
+        assert_eq!(
+            argv(item(&shortcuts(), "man ls", Some(&terminal))).1,
+            ["sh", "-c", "man ls"]
+        );
+
+        // running in a terminal needs one
+        assert!(item(&shortcuts(), "man ls", None).is_none());
+    }
+
+    #[test]
+    fn test_no_match() {
+        // the keyword alone, an unknown one, or one that's just the start of a word
+        assert!(item(&shortcuts(), "gh", None).is_none());
+        assert!(item(&shortcuts(), "gh   ", None).is_none());
+        assert!(item(&shortcuts(), "gl runi", None).is_none());
+        assert!(item(&shortcuts(), "ghost runi", None).is_none());
+    }
+}
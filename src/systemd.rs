@@ -0,0 +1,187 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+};
+
+use anyhow::{Context, Result};
+use zbus::{
+    blocking::Connection,
+    zvariant::{OwnedObjectPath, Value},
+};
+
+#[zbus::proxy(
+    interface = "org.freedesktop.systemd1.Manager",
+    default_service = "org.freedesktop.systemd1",
+    default_path = "/org/freedesktop/systemd1",
+    gen_async = false,
+    blocking_name = "ManagerProxy"
+)]
+trait Manager {
+    fn start_transient_unit(
+        &self,
+        name: &str,
+        mode: &str,
+        properties: &[(&str, Value<'_>)],
+        aux: &[(&str, &[(&str, Value<'_>)])],
+    ) -> zbus::Result<OwnedObjectPath>;
+}
+
+/// The systemd user manager, placing launched apps in scopes of their own
+pub struct Manager {
+    proxy: ManagerProxy<'static>,
+}
+
+impl Manager {
+    /// Connects to the user manager over the session bus.
+    pub fn connect() -> Result<Self> {
+        let connection = Connection::session().context("failed to connect to the session bus")?;
+
+        Self::with_connection(&connection)
+    }
+
+    pub(crate) fn with_connection(connection: &Connection) -> Result<Self> {
+        let proxy = ManagerProxy::new(connection)?;
+
+        Ok(Self { proxy })
+    }
+
+    /// Moves process `pid` of app `id` (a desktop file ID) into a new transient scope.
+    ///
+    /// Returns the name of the scope, `app-runi-<id>-<random>.scope`.
+    pub fn start_scope(&self, id: &str, pid: u32) -> Result<String> {
+        let name = scope_name(id);
+        let properties = [
+            ("Description", Value::from(format!("Application {id}"))),
+            ("PIDs", Value::from(vec![pid])),
+            // don't keep failed scopes around
+            ("CollectMode", Value::from("inactive-or-failed")),
+        ];
+
+        self.proxy
+            .start_transient_unit(&name, "fail", &properties, &[])
+            .with_context(|| format!("failed to start {name}"))?;
+
+        Ok(name)
+    }
+}
+
+/// Scope name following the XDG app naming, `app-<launcher>-<app ID>-<random>.scope`
+fn scope_name(id: &str) -> String {
+    let app_id = id.strip_suffix(".desktop").unwrap_or(id);
+    let random = RandomState::new().build_hasher().finish() as u32;
+
+    format!("app-runi-{}-{random:08x}.scope", escape(app_id))
+}
+
+/// Escapes `s` for a unit name like `systemd-escape`; `-` is escaped too as it separates parts.
+fn escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for (idx, byte) in s.bytes().enumerate() {
+        match byte {
+            b'.' if idx == 0 => escaped.push_str("\\x2e"),
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b':' | b'_' | b'.' => {
+                escaped.push(byte as char)
+            }
+            _ => escaped.push_str(&format!("\\x{byte:02x}")),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{
+        os::unix::net::UnixStream,
+        sync::{Arc, Mutex},
+    };
+
+    use zbus::{Guid, blocking::connection, zvariant::OwnedValue};
+
+    use super::*;
+
+    /// Transient units started on the mock bus, with their properties
+    pub(crate) type Started = Arc<Mutex<Vec<(String, Vec<(String, OwnedValue)>)>>>;
+
+    struct MockManager {
+        started: Started,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.systemd1.Manager")]
+    impl MockManager {
+        fn start_transient_unit(
+            &self,
+            name: String,
+            mode: String,
+            properties: Vec<(String, OwnedValue)>,
+            _aux: Vec<(String, Vec<(String, OwnedValue)>)>,
+        ) -> zbus::fdo::Result<OwnedObjectPath> {
+            if mode != "fail" || !name.ends_with(".scope") {
+                return Err(zbus::fdo::Error::InvalidArgs(name));
+            }
+
+            self.started.lock().unwrap().push((name, properties));
+            Ok(OwnedObjectPath::try_from("/org/freedesktop/systemd1/job/1").unwrap())
+        }
+    }
+
+    /// Serves a mock systemd manager on one end of a socket pair, connecting to the other
+    pub(crate) fn mock_bus(started: Started) -> (Connection, Connection) {
+        let (server, client) = UnixStream::pair().unwrap();
+        let guid = Guid::generate();
+
+        // both ends have to authenticate at once
+        let server = std::thread::spawn(move || {
+            connection::Builder::async_io_unix_stream(server)
+                .server(guid)
+                .unwrap()
+                .p2p()
+                .serve_at("/org/freedesktop/systemd1", MockManager { started })
+                .unwrap()
+                .build()
+                .unwrap()
+        });
+        let client = connection::Builder::async_io_unix_stream(client)
+            .p2p()
+            .build()
+            .unwrap();
+
+        (server.join().unwrap(), client)
+    }
+
+    #[test]
+    fn test_start_scope() {
+        let started = Started::default();
+        let (_server, client) = mock_bus(started.clone());
+        let manager = Manager::with_connection(&client).unwrap();
+
+        let name = manager
+            .start_scope("org.example.my-app.desktop", 1234)
+            .unwrap();
+        assert!(name.starts_with("app-runi-org.example.my\\x2dapp-"));
+        assert!(name.ends_with(".scope"));
+
+        let started = started.lock().unwrap();
+        let (started_name, properties) = &started[0];
+        assert_eq!(started_name, &name);
+
+        let pids = properties
+            .iter()
+            .find(|(property, _)| property == "PIDs")
+            .map(|(_, value)| Vec::<u32>::try_from(value.try_clone().unwrap()).unwrap());
+        assert_eq!(pids, Some(vec![1234]));
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("org.gnome.Nautilus"), "org.gnome.Nautilus");
+        assert_eq!(escape("firefox-esr"), "firefox\\x2desr");
+        assert_eq!(escape(".hidden app"), "\\x2ehidden\\x20app");
+        assert_eq!(escape("ünï"), "\\xc3\\xbcn\\xc3\\xaf");
+
+        // the random parts differ
+        assert_ne!(scope_name("foo.desktop"), scope_name("foo.desktop"));
+        assert!(scope_name("foo.desktop").starts_with("app-runi-foo-"));
+    }
+}
@@ -1,20 +1,123 @@
-use crate::model::Application;
+use crate::model::{Action, Application, Item, TerminalCommand};
 use crate::{
     Lock,
     backend::{UiBackend, eframe, layer_shell},
-    config,
+    calc,
+    config::{Fallback, LaunchMethod, Shortcut, Theme},
+    fallback,
+    fuzzy::Match,
+    history::{self, History},
+    icon,
+    ipc::{Request, Server},
+    shortcut,
 };
 use anyhow::{Context as _, Result};
 use egui::{
-    Align, CentralPanel, CursorIcon, EventFilter, Frame, InnerResponse, Key, Label, Layout, Margin,
-    RichText, ScrollArea, Sense, Stroke, StrokeKind, TextEdit, TextStyle, Ui, UiBuilder, Widget,
+    Align, CentralPanel, Color32, ColorImage, EventFilter, FontData, FontFamily, FontId, Frame, Id,
+    Image, InnerResponse, Key, Label, Layout, Margin, Modifiers, RichText, ScrollArea, Sense,
+    Stroke, StrokeKind, TextEdit, TextStyle, TextureHandle, TextureOptions, Ui, UiBuilder, Vec2,
+    Widget,
+    epaint::text::{FontInsert, FontPriority, InsertFontFamily},
+    load::SizedTexture,
+    text::{LayoutJob, TextFormat},
     vec2,
 };
-use std::{thread::JoinHandle, vec};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write,
+    path::PathBuf,
+    process::{Command, Stdio},
+    str::FromStr,
+    sync::{
+        Arc,
+        mpsc::{self, Receiver},
+    },
+    thread::{self, JoinHandle},
+    vec,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Launch desktop entries
+    #[default]
+    Apps,
+
+    /// Run executables found in `PATH`, with the arguments typed after the command
+    Run,
+
+    /// Pick an emoji or another character and copy it to the clipboard
+    Chars {
+        /// Whether the character is printed to stdout instead
+        print: bool,
+    },
+
+    /// Pick one of the lines read from stdin and print it to stdout
+    Dmenu {
+        /// Whether the typed text can be printed instead of an item
+        print_query: bool,
+    },
+}
+
+impl FromStr for Mode {
+    type Err = anyhow::Error;
 
-pub fn run_ui(apps_thread: JoinHandle<Vec<Application>>, backend: UiBackend, flock: Lock) {
-    let app = LauncherApp::new(apps_thread, flock);
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "apps" => Self::Apps,
+            "run" => Self::Run,
+            "chars" => Self::Chars { print: false },
+            "dmenu" => Self::Dmenu { print_query: false },
+            _ => anyhow::bail!("unknown mode '{s}' (expected apps, run, chars or dmenu)"),
+        })
+    }
+}
+
+/// Command line options and config affecting the UI
+pub struct UiOptions {
+    pub backend: UiBackend,
+    pub mode: Mode,
+    pub prompt: Option<String>,
+    pub theme: Theme,
+    pub launch: LaunchMethod,
+    pub launch_log: Option<PathBuf>,
+    pub fallbacks: Vec<Fallback>,
+    pub shortcuts: Vec<Shortcut>,
+    pub terminal: Option<TerminalCommand>,
+}
 
+/// Loads the items to pick from
+pub type ItemLoader = Arc<dyn Fn() -> Vec<Item> + Send + Sync>;
+
+/// Runs the launcher; other instances can reach it over `server`, if given.
+pub fn run_ui(
+    items_thread: JoinHandle<Vec<Item>>,
+    options: UiOptions,
+    flock: Lock,
+    server: Option<Server>,
+) {
+    let backend = options.backend;
+    let mut app = LauncherApp::new(items_thread, options, flock);
+    app.server = server;
+
+    run_backend(backend, app);
+}
+
+/// Runs a resident launcher: hidden until a client asks to show it over `server`.
+///
+/// Items are loaded again whenever the window gets hidden, so new apps show up.
+pub fn run_daemon(load_items: ItemLoader, options: UiOptions, server: Server) {
+    let backend = options.backend;
+
+    let loader = load_items.clone();
+    let mut app = LauncherApp::new(thread::spawn(move || loader()), options, Lock::unlocked());
+    app.load_items = Some(load_items);
+    app.server = Some(server);
+    app.visible = false;
+
+    run_backend(backend, app);
+}
+
+fn run_backend(backend: UiBackend, app: LauncherApp) {
     let run_backend = match backend {
         UiBackend::LayerShell => layer_shell::run,
         UiBackend::Eframe => eframe::run,
@@ -36,18 +139,64 @@ pub(crate) struct LauncherApp {
     /// File lock
     flock: Option<Lock>,
 
-    /// Application discovery thread
-    apps_thread: Option<JoinHandle<Vec<Application>>>,
+    /// What the list contains and what happens on selection
+    mode: Mode,
+
+    /// Placeholder of the search field
+    prompt: String,
+
+    /// Colors, fonts and sizes
+    theme: Theme,
+
+    /// How apps are started; a resident launcher always detaches them
+    launch: LaunchMethod,
+
+    /// Output of detached apps
+    launch_log: Option<PathBuf>,
+
+    /// Rows offered below the matches
+    fallbacks: Vec<Fallback>,
+
+    /// Keywords leading the query
+    shortcuts: Vec<Shortcut>,
+
+    /// Terminal emulator of the terminal fallback, if known before loading items
+    terminal: Option<TerminalCommand>,
+
+    /// Item discovery thread
+    items_thread: Option<JoinHandle<Vec<Item>>>,
 
-    /// Application list
-    apps: Vec<Application>,
+    /// Item list
+    items: Vec<Item>,
 
-    /// Indices of filtered applications (points to self.apps)
-    filtered_apps: Vec<(usize, f64)>,
+    /// Items derived from the query, e.g. a calculator result (indices in `filtered` past
+    /// `items` point here)
+    query_items: Vec<Item>,
 
-    /// Index of selected application (points to self.filtered_apps)
+    /// Icon textures by icon name
+    icons: HashMap<String, TextureHandle>,
+
+    /// Icons decoded by the icon thread, if icons are shown
+    icons_receiver: Option<Receiver<(String, ColorImage)>>,
+
+    /// Launch history
+    history: History,
+
+    /// Frecency of each item (points to self.items)
+    frecency: Vec<f64>,
+
+    /// Indices of filtered items (points to self.items)
+    filtered: Vec<(usize, Match)>,
+
+    /// Index of selected item (points to self.filtered)
     selected: usize,
 
+    /// Whether actions of the selected item are listed
+    expanded: bool,
+
+    /// Index of selected action (points to actions of the selected item)
+    selected_action: Option<usize>,
+
     /// Search field state
     search_state: String,
 
@@ -56,63 +205,424 @@ pub(crate) struct LauncherApp {
 
     /// Whether the layer-shell event loop should exit.
     closing: bool,
+
+    /// Whether the window is shown; only a resident launcher hides it
+    visible: bool,
+
+    /// Whether the window should grab the focus again
+    focus_requested: bool,
+
+    /// Whether the theme was applied
+    initialized: bool,
+
+    /// Reloads items of a resident launcher
+    load_items: Option<ItemLoader>,
+
+    /// Socket other instances send requests to
+    server: Option<Server>,
+
+    /// Requests received over `server`
+    requests: Option<Receiver<Request>>,
 }
 
 impl LauncherApp {
-    fn new(apps_thread: JoinHandle<Vec<Application>>, flock: Lock) -> Self {
+    fn new(items_thread: JoinHandle<Vec<Item>>, options: UiOptions, flock: Lock) -> Self {
         Self {
             flock: Some(flock),
-            apps_thread: Some(apps_thread),
-            apps: vec![],
-            filtered_apps: vec![],
+            mode: options.mode,
+            prompt: options.prompt.unwrap_or_else(|| "🔎 Search".to_owned()),
+            theme: options.theme,
+            launch: options.launch,
+            launch_log: options.launch_log,
+            fallbacks: options.fallbacks,
+            shortcuts: options.shortcuts,
+            terminal: options.terminal,
+            items_thread: Some(items_thread),
+            items: vec![],
+            query_items: vec![],
+            icons: HashMap::new(),
+            icons_receiver: None,
+            history: History::load_default(),
+            frecency: vec![],
+            filtered: vec![],
             selected: 0,
+            expanded: false,
+            selected_action: None,
             search_state: String::with_capacity(16),
             error: None,
             closing: false,
+            visible: true,
+            focus_requested: false,
+            initialized: false,
+            load_items: None,
+            server: None,
+            requests: None,
         }
     }
 
-    fn exec_app(&mut self) -> Result<()> {
-        drop(self.flock.take());
+    /// Launches the selected item; `use_query` prefers the typed text in dmenu mode.
+    fn activate(&mut self, use_query: bool) -> Result<()> {
+        if let Mode::Dmenu { print_query: true } = self.mode
+            && (use_query || self.filtered.is_empty())
+        {
+            self.print_and_exit(&self.search_state.clone());
+        }
+
+        let Some(&(item_idx, _)) = self.filtered.get(self.selected) else {
+            return Ok(());
+        };
 
-        self.apps[self.filtered_apps[self.selected].0]
-            .exec()
-            .context("Failed to launch application")?;
+        let app = match self.item(item_idx) {
+            Item::App(app) if self.mode == Mode::Run => Box::new(app.with_args(self.run_args()?)),
+            Item::App(app) => app.clone(),
+            // made up, so not worth remembering
+            Item::Command { app, .. } => return self.launch(&app.clone(), None, false),
+            Item::Line(line) => self.print_and_exit(&line.clone()),
+            Item::Char(c) if self.mode == (Mode::Chars { print: true }) => {
+                self.print_and_exit(&c.glyph.clone())
+            }
+            Item::Calc(result) => {
+                copy_to_clipboard(&result.clone())?;
+                self.close();
+                return Ok(());
+            }
+            Item::Char(c) => {
+                copy_to_clipboard(&c.glyph.clone())?;
+                self.close();
+                return Ok(());
+            }
+        };
+
+        let action = self.selected_action.map(|action| &app.actions[action]);
+        self.launch(&app, action, true)
+    }
+
+    /// Starts `app` (or one of its actions) the configured way, recording it if `remember`.
+    fn launch(&mut self, app: &Application, action: Option<&Action>, remember: bool) -> Result<()> {
+        let log = self.launch_log.as_deref();
+        let mut start = || match self.launch {
+            LaunchMethod::Systemd => app.spawn_in_scope(action, log),
+            LaunchMethod::Exec if self.load_items.is_none() => {
+                // nothing gets dropped once the process is replaced
+                drop(self.flock.take());
+                drop(self.server.take());
+
+                match action {
+                    Some(action) => app.exec_action(action),
+                    None => app.exec(),
+                }
+            }
+            LaunchMethod::Exec | LaunchMethod::Detach => app.spawn(action, log),
+        };
+
+        if remember {
+            self.history.record_launch(app, start)
+        } else {
+            start()
+        }
+        .context("Failed to launch application")?;
+
+        // only detached apps get here
+        self.close();
 
         Ok(())
     }
 
+    /// Prints the dmenu selection and exits, like launching an app replaces the process.
+    fn print_and_exit(&mut self, line: &str) -> ! {
+        drop(self.flock.take());
+
+        let mut stdout = std::io::stdout().lock();
+        let _ = writeln!(stdout, "{line}").and_then(|_| stdout.flush());
+
+        std::process::exit(0);
+    }
+
+    /// Text the items are searched for: the command name in run mode, the whole query otherwise
+    fn filter(&self) -> &str {
+        match self.mode {
+            Mode::Run => self
+                .search_state
+                .split_whitespace()
+                .next()
+                .unwrap_or_default(),
+            _ => &self.search_state,
+        }
+    }
+
+    /// Arguments typed after the command name in run mode, split like a shell does
+    fn run_args(&self) -> Result<Vec<String>> {
+        let args = self.search_state.trim_start();
+        let args = args
+            .find(char::is_whitespace)
+            .map_or("", |idx| &args[idx..]);
+
+        shell_words::split(args).context("Invalid arguments")
+    }
+
+    fn item(&self, item_idx: usize) -> &Item {
+        match item_idx.checked_sub(self.items.len()) {
+            Some(query_idx) => &self.query_items[query_idx],
+            None => &self.items[item_idx],
+        }
+    }
+
+    fn selected_item(&self) -> Option<&Item> {
+        self.filtered
+            .get(self.selected)
+            .map(|(item_idx, _)| self.item(*item_idx))
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = selected;
+        self.expanded = false;
+        self.selected_action = None;
+    }
+
+    fn select_next(&mut self) {
+        let actions = match self.selected_item() {
+            Some(item) if self.expanded => item.actions().len(),
+            _ => 0,
+        };
+        let next_action = self.selected_action.map_or(0, |action| action + 1);
+
+        if next_action < actions {
+            self.selected_action = Some(next_action);
+        } else if self.selected + 1 < self.filtered.len() {
+            self.select(self.selected + 1);
+        }
+    }
+
+    fn select_prev(&mut self) {
+        match self.selected_action {
+            Some(0) => self.selected_action = None,
+            Some(action) => self.selected_action = Some(action - 1),
+            None if self.selected > 0 => self.select(self.selected - 1),
+            None => {}
+        }
+    }
+
+    /// Selects the item (or its action) under the pointer.
+    fn point_at(&mut self, selection: usize, action: Option<usize>) {
+        if self.selected != selection {
+            self.select(selection);
+        }
+        self.selected_action = action;
+    }
+
+    fn set_expanded(&mut self, expanded: bool) {
+        let has_actions = self
+            .selected_item()
+            .is_some_and(|item| !item.actions().is_empty());
+
+        self.expanded = expanded && has_actions;
+        self.selected_action = None;
+    }
+
     fn ensure_init(&mut self, ctx: &egui::Context) {
-        if let Some(apps_thread) = self.apps_thread.take() {
-            let apps = apps_thread.join().expect("failed to join apps_thread");
-            let filtered_apps = (0..apps.len()).map(|idx| (idx, 1.0)).collect();
-            self.apps = apps;
-            self.filtered_apps = filtered_apps;
+        if !self.initialized {
+            self.apply_theme(ctx);
+            self.initialized = true;
         }
 
-        ctx.output_mut(|o| o.cursor_icon = CursorIcon::None);
+        // the first load is waited for, reloads replace the items once they're done
+        if let Some(items_thread) = self
+            .items_thread
+            .take_if(|thread| self.items.is_empty() || thread.is_finished())
+        {
+            let now = history::now();
+            self.items = items_thread.join().expect("failed to join items_thread");
+            self.frecency = self
+                .items
+                .iter()
+                .map(|item| match item {
+                    Item::App(app) => self.history.frecency(app, now),
+                    _ => 0.0,
+                })
+                .collect();
+            self.on_search_update();
+            self.load_icons(ctx);
+        }
+
+        if let Some(receiver) = &self.icons_receiver {
+            for (name, image) in receiver.try_iter() {
+                let texture = ctx.load_texture(&name, image, TextureOptions::LINEAR);
+                self.icons.insert(name, texture);
+            }
+        }
     }
 
-    fn on_search_update(&mut self) {
-        if self.search_state.is_empty() {
-            self.filtered_apps = (0..self.apps.len()).map(|idx| (idx, 1.0)).collect();
-            self.selected = 0;
+    /// Starts decoding icons of all items in the background.
+    fn load_icons(&mut self, ctx: &egui::Context) {
+        let icons: HashSet<String> = self
+            .items
+            .iter()
+            .filter_map(Item::icon)
+            .chain(
+                QUERY_ICONS
+                    .iter()
+                    .chain(fallback::ICONS)
+                    .chain(shortcut::ICONS)
+                    .copied(),
+            )
+            .filter(|icon| !self.icons.contains_key(*icon))
+            .map(str::to_owned)
+            .collect();
+
+        // a reload keeps the receiver (and with it the icon column) if all icons are known
+        if !self.theme.icons || icons.is_empty() {
             return;
         }
 
-        self.filtered_apps.clear();
+        let repaint = ctx.clone();
+        self.icons_receiver = Some(icon::load_icons(
+            icons,
+            self.theme.icon_theme.clone(),
+            self.theme.icon_size,
+            ctx.pixels_per_point().ceil() as u32,
+            move || repaint.request_repaint(),
+        ));
+    }
+
+    /// Sets up the parts of the theme that egui draws on its own.
+    fn apply_theme(&self, ctx: &egui::Context) {
+        let theme = &self.theme;
+
+        if let Mode::Chars { .. } = self.mode {
+            add_fallback_fonts(ctx);
+        }
+
+        let family = match theme.font_family.as_deref() {
+            None | Some("proportional") => FontFamily::Proportional,
+            Some("monospace") => FontFamily::Monospace,
+            Some(family) => match load_font(family) {
+                Ok(font) => {
+                    ctx.add_font(FontInsert::new(
+                        family,
+                        FontData::from_owned(font),
+                        vec![InsertFontFamily {
+                            family: FontFamily::Proportional,
+                            priority: FontPriority::Highest,
+                        }],
+                    ));
+                    FontFamily::Proportional
+                }
+                Err(err) => {
+                    log::warn!("failed to load font '{family}': {err:#}");
+                    FontFamily::Proportional
+                }
+            },
+        };
 
-        for (app_idx, app) in self.apps.iter().enumerate() {
-            let score = app.score(&self.search_state);
+        ctx.all_styles_mut(|style| {
+            style.text_styles.insert(
+                TextStyle::Heading,
+                FontId::new(theme.font_size, family.clone()),
+            );
+            style.text_styles.insert(
+                TextStyle::Body,
+                FontId::new(theme.font_size * 0.75, family.clone()),
+            );
 
-            if score > 0.05 {
-                self.filtered_apps.push((app_idx, score));
+            style.visuals.override_text_color = theme.text_color;
+            if let Some(background) = theme.background_color {
+                style.visuals.window_fill = background;
             }
+        });
+    }
+
+    fn on_search_update(&mut self) {
+        self.filtered.clear();
+
+        let filter = self.filter().to_owned();
+        if filter.is_empty() {
+            let all = Match {
+                score: 1.0,
+                indices: vec![],
+            };
+            self.filtered
+                .extend((0..self.items.len()).map(|idx| (idx, all.clone())));
+        } else {
+            for (item_idx, item) in self.items.iter().enumerate() {
+                if let Some(m) = item.score(&filter)
+                    && m.score > 0.05
+                {
+                    self.filtered.push((item_idx, m));
+                }
+            }
+        }
+
+        // sort by score, then by frecency (reversed order); the sort is stable, so items
+        // without history keep their order
+        self.filtered.sort_by(|a, b| {
+            b.1.score
+                .total_cmp(&a.1.score)
+                .then_with(|| self.frecency[b.0].total_cmp(&self.frecency[a.0]))
+        });
+
+        self.update_query_items();
+        self.select(0);
+    }
+
+    /// Filters again after the query was edited from `previous_filter`.
+    ///
+    /// Typing arguments in run mode leaves the filter alone, so the executable picked with the
+    /// arrow keys stays selected.
+    fn on_search_edit(&mut self, previous_filter: &str) {
+        let selected = self
+            .filtered
+            .get(self.selected)
+            .map(|&(item_idx, _)| item_idx);
+        self.on_search_update();
+
+        if self.filter() == previous_filter
+            && let Some(selected) = selected
+            && let Some(idx) = self.filtered.iter().position(|&(i, _)| i == selected)
+        {
+            self.select(idx);
+        }
+    }
+
+    /// Derives items from the query, listing shortcuts and the calculator result above the
+    /// matches and fallbacks below them.
+    fn update_query_items(&mut self) {
+        self.query_items.clear();
+
+        if self.mode == Mode::Apps {
+            let terminal = self.terminal();
+            let shortcut = shortcut::item(&self.shortcuts, &self.search_state, terminal);
+            let calc = calc::evaluate_query(&self.search_state).map(Item::Calc);
+
+            self.query_items = shortcut.into_iter().chain(calc).collect();
         }
+        let top = self.query_items.len();
+
+        if matches!(self.mode, Mode::Apps | Mode::Run) {
+            let fallbacks = fallback::items(&self.fallbacks, &self.search_state, self.terminal());
+            self.query_items.extend(fallbacks);
+        }
+
+        let first = self.items.len();
+        let row = |item_idx| {
+            let m = Match {
+                score: 1.0,
+                indices: vec![],
+            };
+            (item_idx, m)
+        };
+        self.filtered.splice(0..0, (first..first + top).map(row));
+        self.filtered
+            .extend((first + top..first + self.query_items.len()).map(row));
+    }
 
-        // sort by score (reversed order)
-        self.filtered_apps.sort_by(|a, b| b.1.total_cmp(&a.1));
-        self.selected = 0;
+    /// The configured terminal emulator, or the one found for apps with `Terminal=true`
+    fn terminal(&self) -> Option<&TerminalCommand> {
+        self.terminal.as_ref().or_else(|| {
+            self.items.iter().find_map(|item| match item {
+                Item::App(app) => app.terminal_command.as_ref(),
+                _ => None,
+            })
+        })
     }
 
     fn on_error(&mut self, err: anyhow::Error) {
@@ -123,8 +633,21 @@ impl LauncherApp {
         self.error = None;
     }
 
+    /// Whether the text cursor of the search field is at the end of the query, or not placed yet.
+    fn cursor_at_end(&self, ctx: &egui::Context) -> bool {
+        let len = self.search_state.chars().count();
+
+        TextEdit::load_state(ctx, Id::new(SEARCH_ID))
+            .and_then(|state| state.cursor.char_range())
+            .is_none_or(|range| range.primary.index == len && range.secondary.index == len)
+    }
+
     fn check_input(&mut self, ctx: &egui::Context) -> Result<()> {
-        let should_close = ctx.input(|input| {
+        // the arrows move the text cursor too, so they only expand or collapse actions at the end
+        // of the query and are consumed then
+        let cursor_at_end = self.cursor_at_end(ctx);
+
+        let should_close = ctx.input_mut(|input| {
             let mut close = false;
 
             if input.key_pressed(Key::Escape) {
@@ -134,18 +657,27 @@ impl LauncherApp {
                     close = true;
                 }
             } else if input.key_pressed(Key::ArrowDown) {
-                self.selected = (self.selected + 1).min(self.filtered_apps.len().saturating_sub(1));
+                self.select_next();
             } else if input.key_pressed(Key::ArrowUp) {
-                self.selected = self.selected.saturating_sub(1);
+                self.select_prev();
+            } else if input.key_pressed(Key::Tab) {
+                self.set_expanded(!self.expanded);
+            } else if cursor_at_end && input.consume_key(Modifiers::NONE, Key::ArrowRight) {
+                self.set_expanded(true);
+            } else if cursor_at_end
+                && self.expanded
+                && input.consume_key(Modifiers::NONE, Key::ArrowLeft)
+            {
+                self.set_expanded(false);
             } else if input.key_pressed(Key::Enter) {
-                self.exec_app()?;
+                self.activate(input.modifiers.shift)?;
             }
 
             anyhow::Ok(close)
         })?;
 
         if should_close {
-            self.closing = true;
+            self.close();
         }
 
         Ok(())
@@ -154,15 +686,119 @@ impl LauncherApp {
 
 impl LauncherApp {
     pub fn clear_color(&self) -> [f32; 4] {
-        config::BACKGROUND_COLOR.to_normalized_gamma_f32()
+        // the window is transparent outside of the rounded frame
+        Color32::TRANSPARENT.to_normalized_gamma_f32()
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.theme.width, self.theme.height)
     }
 
     pub fn closing(&self) -> bool {
         self.closing
     }
 
+    /// Exits, or just hides the window of a resident launcher.
     pub fn close(&mut self) {
-        self.closing = true;
+        if self.load_items.is_some() {
+            self.hide();
+        } else {
+            self.closing = true;
+        }
+    }
+
+    pub fn visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Whether the window should grab the focus again, clearing the request.
+    pub fn take_focus_request(&mut self) -> bool {
+        std::mem::take(&mut self.focus_requested)
+    }
+
+    /// Prepares fonts and items, so a hidden window shows up without delay.
+    pub fn warm_up(&mut self, ctx: &egui::Context) {
+        self.ensure_init(ctx);
+    }
+
+    /// Starts receiving requests of clients, calling `wake` after each one.
+    ///
+    /// Requests are handled by `handle_requests`, which the backend calls once woken up.
+    pub fn serve(&mut self, wake: impl Fn() + Send + 'static) {
+        let Some(server) = &self.server else {
+            return;
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        let result = server.serve(move |request| {
+            let _ = sender.send(request);
+            wake();
+        });
+
+        match result {
+            Ok(()) => self.requests = Some(receiver),
+            Err(err) => log::warn!("failed to handle requests of other instances: {err:#}"),
+        }
+    }
+
+    /// Shows, focuses or closes the window as asked by other instances.
+    pub fn handle_requests(&mut self) {
+        let Some(requests) = &self.requests else {
+            return;
+        };
+
+        for request in requests.try_iter().collect::<Vec<_>>() {
+            match request {
+                Request::Toggle if self.visible => self.close(),
+                Request::Show | Request::Toggle => {
+                    self.visible = true;
+                    self.focus_requested = true;
+                }
+            }
+        }
+    }
+
+    /// A hidden resident launcher without items, receiving the requests sent to the returned end.
+    #[cfg(test)]
+    pub fn test_daemon() -> (Self, mpsc::Sender<Request>) {
+        let options = UiOptions {
+            backend: UiBackend::default(),
+            mode: Mode::Apps,
+            prompt: None,
+            theme: Theme::default(),
+            launch: LaunchMethod::default(),
+            launch_log: None,
+            fallbacks: vec![],
+            shortcuts: vec![],
+            terminal: None,
+        };
+        let mut app = Self::new(thread::spawn(Vec::new), options, Lock::unlocked());
+        app.load_items = Some(Arc::new(Vec::new));
+        app.visible = false;
+
+        let (sender, receiver) = mpsc::channel();
+        app.requests = Some(receiver);
+
+        (app, sender)
+    }
+
+    /// Hides the window, starting the next search afresh with reloaded items.
+    fn hide(&mut self) {
+        if !self.visible {
+            return;
+        }
+        self.visible = false;
+
+        self.search_state.clear();
+        self.reset_error();
+        self.on_search_update();
+
+        if let Some(load_items) = &self.load_items
+            && self.items_thread.is_none()
+        {
+            let load_items = load_items.clone();
+            self.items_thread = Some(thread::spawn(move || load_items()));
+        }
     }
 
     pub fn update(&mut self, root: &mut Ui) {
@@ -185,10 +821,16 @@ impl LauncherApp {
                 });
         }
 
-        let text_color = ctx.global_style().visuals.text_color();
+        let theme = self.theme.clone();
+        let visuals = &ctx.global_style().visuals;
+        let border = Stroke::new(
+            theme.border_width,
+            theme.border_color.unwrap_or(visuals.text_color()),
+        );
+        let fill = visuals.window_fill();
 
         // Height of the title bar
-        let height = 32.0;
+        let height = theme.search_height;
 
         CentralPanel::default()
             .frame(Frame::NONE)
@@ -202,9 +844,9 @@ impl LauncherApp {
                 // Frame
                 painter.rect(
                     rect.shrink(1.0),
-                    10.0,
-                    ctx.global_style().visuals.window_fill(),
-                    Stroke::new(1.0, text_color),
+                    theme.radius,
+                    fill,
+                    border,
                     StrokeKind::Inside,
                 );
 
@@ -214,7 +856,7 @@ impl LauncherApp {
                         rect.left_top() + vec2(2.0, height),
                         rect.right_top() + vec2(-2.0, height),
                     ],
-                    Stroke::new(1.0, text_color),
+                    border,
                 );
 
                 let search_rect = {
@@ -222,14 +864,16 @@ impl LauncherApp {
                     rect.max.y = rect.min.y + height;
                     rect
                 }
-                .shrink(4.0);
+                .shrink(theme.search_padding);
 
+                let filter = self.filter().to_owned();
                 let search_response = ui.put(
                     search_rect,
                     TextEdit::singleline(&mut self.search_state)
+                        .id(Id::new(SEARCH_ID))
                         .font(TextStyle::Heading)
                         .frame(Frame::NONE.inner_margin(Margin::symmetric(4, 2)))
-                        .hint_text(RichText::new("🔎 Search").text_style(TextStyle::Heading)),
+                        .hint_text(RichText::new(&self.prompt).text_style(TextStyle::Heading)),
                 );
 
                 // always focus on search
@@ -247,7 +891,7 @@ impl LauncherApp {
                 });
 
                 if search_response.changed() {
-                    self.on_search_update();
+                    self.on_search_edit(&filter);
                 }
 
                 let content_rect = {
@@ -255,7 +899,7 @@ impl LauncherApp {
                     rect.min.y = search_rect.max.y;
                     rect
                 }
-                .shrink(8.0);
+                .shrink(theme.padding);
 
                 let mut application_list_ui =
                     ui.new_child(UiBuilder::new().max_rect(content_rect).layout(*ui.layout()));
@@ -263,29 +907,132 @@ impl LauncherApp {
                     // justify apps for better mouse interaction
                     let list_layout = Layout::top_down(Align::Min).with_cross_justify(true);
 
+                    // selection follows the pointer only when it moves, not when the list
+                    // moves under a resting pointer
+                    let pointer_moved = ui.input(|input| input.pointer.delta() != Vec2::ZERO);
+                    let mut pointed = None;
+                    let mut clicked = false;
+
                     // draw filtered applications
                     let result: InnerResponse<Result<(), anyhow::Error>> =
                         ui.with_layout(list_layout, |ui| {
-                            for (selection, (app_idx, _)) in self.filtered_apps.iter().enumerate() {
-                                let mut selected = false;
-                                let app_name = &self.apps[*app_idx].name;
-                                let mut app_name_widget =
-                                    RichText::new(app_name).text_style(TextStyle::Heading);
+                            for (selection, (item_idx, m)) in self.filtered.iter().enumerate() {
+                                let item = self.item(*item_idx);
 
                                 // apply highlight to selected application
-                                if self.selected == selection {
-                                    app_name_widget =
-                                        app_name_widget.background_color(config::SELECTION_COLOR);
-                                    selected = true;
+                                let selected =
+                                    self.selected == selection && self.selected_action.is_none();
+                                let background = if selected {
+                                    theme.selection_color
+                                } else {
+                                    Color32::TRANSPARENT
+                                };
+
+                                // entries shown only thanks to `show_filtered` are greyed-out
+                                let text_color = match item.filtered() {
+                                    Some(_) => ui.visuals().weak_text_color(),
+                                    None => ui.visuals().text_color(),
+                                };
+
+                                let mut name_widget = highlight_matches(
+                                    ui,
+                                    item.name(),
+                                    &m.indices,
+                                    text_color,
+                                    theme.match_color,
+                                    background,
+                                );
+                                if let Some(note) = item.note() {
+                                    name_widget.append(
+                                        &format!("  {note}"),
+                                        0.0,
+                                        TextFormat {
+                                            font_id: TextStyle::Body.resolve(ui.style()),
+                                            color: text_color,
+                                            ..Default::default()
+                                        },
+                                    );
                                 }
+                                let label = Label::new(name_widget);
+
+                                let response = if let Some(glyph) = item.glyph() {
+                                    ui.horizontal(|ui| {
+                                        // a fixed width keeps the names aligned
+                                        let size = Vec2::new(
+                                            theme.font_size * 2.0,
+                                            theme.icon_size as f32,
+                                        );
+                                        ui.add_sized(
+                                            size,
+                                            Label::new(RichText::new(glyph).size(theme.font_size))
+                                                .selectable(false),
+                                        );
+
+                                        label.sense(Sense::focusable_noninteractive()).ui(ui);
+                                    })
+                                    .response
+                                    .interact(Sense::click())
+                                } else if self.icons_receiver.is_some() {
+                                    ui.horizontal(|ui| {
+                                        let size = Vec2::splat(theme.icon_size as f32);
+                                        let texture =
+                                            item.icon().and_then(|icon| self.icons.get(icon));
 
-                                let label = Label::new(app_name_widget)
-                                    .sense(Sense::focusable_noninteractive());
-                                let response = label.ui(ui);
+                                        match texture {
+                                            Some(texture) => {
+                                                Image::new(SizedTexture::new(texture.id(), size))
+                                                    .ui(ui);
+                                            }
+                                            None => {
+                                                ui.allocate_space(size);
+                                            }
+                                        }
+
+                                        label.sense(Sense::focusable_noninteractive()).ui(ui);
+                                    })
+                                    .response
+                                    .interact(Sense::click())
+                                } else {
+                                    label.sense(Sense::click()).ui(ui)
+                                };
 
                                 if selected {
                                     response.scroll_to_me(None);
                                 }
+
+                                if response.clicked() || (pointer_moved && response.hovered()) {
+                                    pointed = Some((selection, None));
+                                    clicked |= response.clicked();
+                                }
+
+                                if self.selected != selection || !self.expanded {
+                                    continue;
+                                }
+
+                                // draw actions of the expanded application
+                                for (action_idx, action) in item.actions().iter().enumerate() {
+                                    let mut action_widget =
+                                        RichText::new(format!("    {}", action.name))
+                                            .text_style(TextStyle::Body);
+
+                                    let selected = self.selected_action == Some(action_idx);
+                                    if selected {
+                                        action_widget =
+                                            action_widget.background_color(theme.selection_color);
+                                    }
+
+                                    let response =
+                                        Label::new(action_widget).sense(Sense::click()).ui(ui);
+
+                                    if selected {
+                                        response.scroll_to_me(None);
+                                    }
+
+                                    if response.clicked() || (pointer_moved && response.hovered()) {
+                                        pointed = Some((selection, Some(action_idx)));
+                                        clicked |= response.clicked();
+                                    }
+                                }
                             }
 
                             anyhow::Ok(())
@@ -294,7 +1041,154 @@ impl LauncherApp {
                     if let Err(err) = result.inner {
                         self.on_error(err)
                     }
+
+                    if let Some((selection, action)) = pointed {
+                        self.point_at(selection, action);
+
+                        if clicked && let Err(err) = self.activate(false) {
+                            self.on_error(err);
+                        }
+                    }
                 })
             });
     }
 }
+
+/// Salt of the search field ID
+const SEARCH_ID: &str = "search";
+
+/// Icons of items derived from the query, loaded along with the icons of all items
+const QUERY_ICONS: &[&str] = &["accessories-calculator"];
+
+/// Copies `text` with `wl-copy`, or `xclip` or `xsel` on X11.
+fn copy_to_clipboard(text: &str) -> Result<()> {
+    let commands: &[&[&str]] = if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        &[&["wl-copy"]]
+    } else {
+        &[
+            &["xclip", "-selection", "clipboard"],
+            &["xsel", "--clipboard", "--input"],
+        ]
+    };
+
+    for command in commands {
+        let child = Command::new(command[0])
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .spawn();
+
+        let mut child = match child {
+            Ok(child) => child,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => return Err(err).with_context(|| format!("failed to run {}", command[0])),
+        };
+
+        // the tools keep serving the clipboard in the background once they read the text
+        child
+            .stdin
+            .take()
+            .context("no stdin")?
+            .write_all(text.as_bytes())?;
+        let status = child.wait()?;
+        anyhow::ensure!(status.success(), "{} failed with {status}", command[0]);
+
+        return Ok(());
+    }
+
+    let names: Vec<_> = commands.iter().map(|command| command[0]).collect();
+    anyhow::bail!("Copying needs {} to be installed", names.join(" or "))
+}
+
+/// Runs `fc-match` to find the file of a font family and reads it.
+fn load_font(family: &str) -> Result<Vec<u8>> {
+    let file = match_font(family)?;
+    std::fs::read(&file).with_context(|| format!("failed to read {file}"))
+}
+
+/// File of the font best matching a fontconfig pattern, e.g. a family name
+fn match_font(pattern: &str) -> Result<String> {
+    let output = Command::new("fc-match")
+        .args(["--format=%{file}", pattern])
+        .output()
+        .context("failed to run fc-match")?;
+    anyhow::ensure!(output.status.success(), "fc-match failed");
+
+    String::from_utf8(output.stdout).context("invalid font path")
+}
+
+/// Adds installed fonts covering emoji and symbols, tried after the ones egui bundles.
+fn add_fallback_fonts(ctx: &egui::Context) {
+    // egui can't draw color emoji fonts; the charset is arrows, math, shapes and dingbats
+    const PATTERNS: &[&str] = &[
+        "emoji:color=false",
+        ":charset=2190-21ff 2200-22ff 2300-23ff 25a0-25ff 2600-26ff 2700-27bf",
+    ];
+
+    // several patterns may resolve to the same font, e.g. the default one
+    let mut files = HashSet::new();
+    for pattern in PATTERNS {
+        let file = match match_font(pattern) {
+            Ok(file) => file,
+            Err(err) => {
+                log::warn!("failed to find a fallback font for '{pattern}': {err:#}");
+                continue;
+            }
+        };
+        if !files.insert(file.clone()) {
+            continue;
+        }
+
+        match std::fs::read(&file) {
+            Ok(font) => {
+                log::debug!("fallback font for '{pattern}': {file}");
+                ctx.add_font(FontInsert::new(
+                    &file,
+                    FontData::from_owned(font),
+                    vec![InsertFontFamily {
+                        family: FontFamily::Proportional,
+                        priority: FontPriority::Lowest,
+                    }],
+                ));
+            }
+            Err(err) => log::warn!("failed to read {file}: {err}"),
+        }
+    }
+}
+
+/// Lays out `text` with the characters at `indices` (not bytes) highlighted.
+fn highlight_matches(
+    ui: &Ui,
+    text: &str,
+    indices: &[usize],
+    text_color: Color32,
+    match_color: Color32,
+    background: Color32,
+) -> LayoutJob {
+    let font_id = TextStyle::Heading.resolve(ui.style());
+    let format = |matched: bool| TextFormat {
+        font_id: font_id.clone(),
+        color: if matched { match_color } else { text_color },
+        background,
+        ..Default::default()
+    };
+
+    let mut job = LayoutJob::default();
+    let mut run = String::new();
+    let mut run_matched = false;
+
+    for (idx, c) in text.chars().enumerate() {
+        let matched = indices.contains(&idx);
+
+        if matched != run_matched && !run.is_empty() {
+            job.append(&run, 0.0, format(run_matched));
+            run.clear();
+        }
+
+        run.push(c);
+        run_matched = matched;
+    }
+
+    job.append(&run, 0.0, format(run_matched));
+    job
+}